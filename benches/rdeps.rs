@@ -0,0 +1,82 @@
+//! reverse dependencies of one package in a synthetic workspace of 5000 cargo packages
+//! `full` builds the graph of every package, `lazy` only infers what `ReverseIndex` says can be affected
+//! `query` answers `rdeps` on an already built graph, for every package in turn, like a long running service would
+//! packages are in 50 layers of 100, each depending on 3 packages of the layer below
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use nabs::{MockRepo, RawTarget, Target, TargetGraph, TargetId, Workspace};
+
+const LAYERS: usize = 50;
+const WIDTH: usize = 100;
+
+fn name(layer: usize, i: usize) -> String {
+    format!("libs/l{}/p{}", layer, i)
+}
+
+fn workspace() -> Workspace {
+    let mut fake = HashMap::new();
+    for layer in 0..LAYERS {
+        for i in 0..WIDTH {
+            let mut manifest =
+                format!("[package]\nname = \"l{}p{}\"\n\n[dependencies]\n", layer, i);
+            if layer > 0 {
+                for j in [i, (i + 1) % WIDTH, (i + WIDTH / 2) % WIDTH] {
+                    manifest.push_str(&format!(
+                        "l{}p{} = {{ path = \"../../l{}/p{}\" }}\n",
+                        layer - 1,
+                        j,
+                        layer - 1,
+                        j
+                    ));
+                }
+            }
+            fake.insert(format!("{}/nabs.json", name(layer, i)), String::new());
+            fake.insert(format!("{}/Cargo.toml", name(layer, i)), manifest);
+        }
+    }
+    Workspace::from_repo(Arc::new(MockRepo::new(fake, PathBuf::new()))).unwrap()
+}
+
+fn rdeps((graph, targets): (TargetGraph, Vec<Target>), changed: &RawTarget) -> usize {
+    let ours: Vec<Target> = targets
+        .into_iter()
+        .filter(|t| t.name == changed.name)
+        .collect();
+    graph.rdeps(&ours).unwrap().len()
+}
+
+fn bench_rdeps(c: &mut Criterion) {
+    let workspace = workspace();
+    let mut group = c.benchmark_group("rdeps");
+    group.sample_size(10);
+    for layer in [LAYERS - 2, LAYERS / 2] {
+        let changed = [RawTarget::from_string_name(name(layer, 0)).unwrap()];
+        let full = rdeps(workspace.build_graph().unwrap(), &changed[0]);
+        let lazy = rdeps(workspace.build_rdeps_graph(&changed).unwrap(), &changed[0]);
+        assert_eq!(full, lazy);
+
+        group.bench_function(format!("full/l{}", layer), |b| {
+            b.iter(|| rdeps(workspace.build_graph().unwrap(), &changed[0]))
+        });
+        group.bench_function(format!("lazy/l{}", layer), |b| {
+            b.iter(|| rdeps(workspace.build_rdeps_graph(&changed).unwrap(), &changed[0]))
+        });
+    }
+    group.finish();
+}
+
+fn bench_queries(c: &mut Criterion) {
+    let (graph, targets) = workspace().build_graph().unwrap();
+    let ids: Vec<TargetId> = targets.iter().map(|t| graph.id(t).unwrap()).collect();
+    let mut i = 0;
+    c.bench_function("rdeps/query", |b| {
+        b.iter(|| {
+            i = (i + 1) % ids.len();
+            graph.rdeps_ids(&ids[i..i + 1]).len()
+        })
+    });
+}
+
+criterion_group!(benches, bench_rdeps, bench_queries);
+criterion_main!(benches);
@@ -0,0 +1,462 @@
+/// on-disk cache of inferred targets, in `.nabs/cache` at the workspace root
+/// an entry stores the `Single`s inferred for a package, along with every file the inferrers read and every directory they listed
+/// an entry is used only if all of these still hash to the same value, otherwise the package is inferred again
+/// only `Infer` results are cached, `LinkInfer`s always run since they can depend on the whole workspace
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result, anyhow};
+use log::info;
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::config::WORKSPACE_FILE_NAME;
+use crate::diagnostics::Location;
+use crate::infer::{FailedParent, Single};
+use crate::types::{RawTarget, Repository, Target};
+
+/// relative to the workspace root
+pub const CACHE_DIR: &str = ".nabs/cache";
+const INFERENCE_FILE_NAME: &str = "inference.json";
+
+thread_local! {
+    // inputs read by the package being inferred on this thread, `None` when nothing is being recorded
+    static RECORDING: RefCell<Option<Vec<Input>>> = const { RefCell::new(None) };
+}
+
+/// something an inferrer looked at, along with the hash of what it saw
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Input {
+    /// `hash` is `None` for a missing file, creating it invalidates the entry
+    File {
+        path: PathBuf,
+        hash: Option<u64>,
+    },
+    Dir {
+        path: PathBuf,
+        hash: u64,
+    },
+}
+
+impl Input {
+    fn is_unchanged(&self, repo: &dyn Repository) -> bool {
+        match self {
+            Input::File { path, hash } => match repo.get_content(path) {
+                Ok(content) => content.as_deref().map(hash_str) == *hash,
+                Err(_) => false,
+            },
+            Input::Dir { path, hash } => hash_listing(&repo.list_files(path)) == *hash,
+        }
+    }
+}
+
+fn hash_str(s: &str) -> u64 {
+    xxh3_64(s.as_bytes())
+}
+
+fn hash_listing(files: &[PathBuf]) -> u64 {
+    let mut files: Vec<String> = files
+        .iter()
+        .map(|f| f.to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    hash_str(&files.join("\n"))
+}
+
+fn record(input: Input) {
+    RECORDING.with(|r| {
+        if let Some(inputs) = r.borrow_mut().as_mut() {
+            inputs.push(input);
+        }
+    });
+}
+
+/// runs `f` and returns every input read through a `RecordingRepo` on this thread meanwhile
+pub(crate) fn recording<T>(f: impl FnOnce() -> T) -> (T, Vec<Input>) {
+    let outer = RECORDING.with(|r| r.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let inputs = RECORDING.with(|r| std::mem::replace(&mut *r.borrow_mut(), outer));
+    (result, inputs.unwrap_or_default())
+}
+
+/// a repository which records what is read through it, see `recording`
+/// inferrers are given this instead of the actual repository when the cache is enabled
+pub struct RecordingRepo {
+    inner: Arc<dyn Repository>,
+}
+
+impl RecordingRepo {
+    pub fn new(inner: Arc<dyn Repository>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Repository for RecordingRepo {
+    fn get_content(&self, path: &Path) -> Result<Option<String>> {
+        let content = self.inner.get_content(path)?;
+        record(Input::File {
+            path: path.to_path_buf(),
+            hash: content.as_deref().map(hash_str),
+        });
+        Ok(content)
+    }
+
+    fn workspace_root(&self) -> &Path {
+        self.inner.workspace_root()
+    }
+
+    fn list_files(&self, dir: &Path) -> Vec<PathBuf> {
+        let files = self.inner.list_files(dir);
+        record(Input::Dir {
+            path: dir.to_path_buf(),
+            hash: hash_listing(&files),
+        });
+        files
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFailedParent {
+    name: String,
+    reason: String,
+    location: Option<Location>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSingle {
+    name: String,
+    flavor: String,
+    parents: Vec<String>,
+    failed_parents: Vec<CachedFailedParent>,
+}
+
+impl CachedSingle {
+    fn from_single(s: &Single) -> Self {
+        CachedSingle {
+            name: s.target.name.to_string(),
+            flavor: s.target.flavor.clone(),
+            parents: s.parents.iter().map(|p| p.name.to_string()).collect(),
+            failed_parents: s
+                .failed_parents
+                .iter()
+                .map(|f| CachedFailedParent {
+                    name: f.name.clone(),
+                    reason: f.reason.clone(),
+                    location: f.location.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    fn to_single(&self) -> Result<Single> {
+        Ok(Single {
+            target: Target::from_string_name(self.name.clone(), self.flavor.clone())?,
+            parents: self
+                .parents
+                .iter()
+                .map(|p| RawTarget::from_string_name(p.clone()))
+                .collect::<Result<_>>()?,
+            failed_parents: self
+                .failed_parents
+                .iter()
+                .map(|f| FailedParent {
+                    name: f.name.clone(),
+                    reason: f.reason.clone(),
+                    location: f.location.clone(),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    inputs: Vec<Input>,
+    singles: Vec<CachedSingle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CacheFile {
+    /// entries are thrown away when this changes, see `fingerprint`
+    fingerprint: u64,
+    entries: HashMap<String, Entry>,
+}
+
+/// same as `CacheFile`, without copying the entries for writing
+#[derive(Serialize)]
+struct CacheFileRef<'a> {
+    fingerprint: u64,
+    entries: HashMap<&'a str, &'a Entry>,
+}
+
+pub struct InferenceCache {
+    repo: Arc<dyn Repository>,
+    fingerprint: u64,
+    // entries read from disk are never modified, so packages can be looked up in parallel without locking
+    loaded: HashMap<String, Entry>,
+    // entries for packages inferred in this run
+    added: Mutex<HashMap<String, Entry>>,
+}
+
+impl InferenceCache {
+    /// the cache of the workspace `repo` belongs to, this is empty if there is no cache yet or if it is unusable
+    pub fn load(repo: Arc<dyn Repository>) -> Result<Self> {
+        let fingerprint = fingerprint(repo.as_ref())?;
+        let path = cache_file(repo.as_ref());
+        let entries = match std::fs::read_to_string(&path) {
+            Err(_) => HashMap::new(),
+            Ok(content) => match serde_json::from_str::<CacheFile>(&content) {
+                Ok(file) if file.fingerprint == fingerprint => file.entries,
+                Ok(_) => {
+                    info!(
+                        "cache: nabs or workspace.json changed, discarding {:?}",
+                        path
+                    );
+                    HashMap::new()
+                }
+                Err(e) => {
+                    info!("cache: failed in parsing {:?}, discarding it. {}", path, e);
+                    HashMap::new()
+                }
+            },
+        };
+        Ok(InferenceCache {
+            repo,
+            fingerprint,
+            loaded: entries,
+            added: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// the cached targets of a package, `None` if there are none or if anything the inferrers read has changed since
+    pub(crate) fn get(&self, raw: &RawTarget) -> Option<Vec<Single>> {
+        let entry = self.loaded.get(raw.name.to_string_ref())?;
+        if !entry
+            .inputs
+            .iter()
+            .all(|i| i.is_unchanged(self.repo.as_ref()))
+        {
+            info!("cache: inputs changed for package={}", raw);
+            return None;
+        }
+        entry
+            .singles
+            .iter()
+            .map(|s| s.to_single())
+            .collect::<Result<_>>()
+            .ok()
+    }
+
+    pub(crate) fn put(&self, raw: &RawTarget, inputs: Vec<Input>, singles: &[Single]) {
+        let entry = Entry {
+            inputs,
+            singles: singles.iter().map(CachedSingle::from_single).collect(),
+        };
+        self.added
+            .lock()
+            .unwrap()
+            .insert(raw.name.to_string(), entry);
+    }
+
+    /// writes the cache to disk if anything was added to it
+    pub fn save(&self) -> Result<()> {
+        let added = self.added.lock().unwrap();
+        if added.is_empty() {
+            return Ok(());
+        }
+        let path = cache_file(self.repo.as_ref());
+        let dir = path
+            .parent()
+            .ok_or(anyhow!("invalid cache path {:?}", path))?;
+        std::fs::create_dir_all(dir).context(anyhow!("failed in creating {:?}", dir))?;
+        let mut entries: HashMap<&str, &Entry> =
+            self.loaded.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        entries.extend(added.iter().map(|(k, v)| (k.as_str(), v)));
+        let file = CacheFileRef {
+            fingerprint: self.fingerprint,
+            entries,
+        };
+        // written to a temporary file first, so that a concurrent run never reads a partial cache
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp, serde_json::to_string(&file)?)
+            .context(anyhow!("failed in writing {:?}", tmp))?;
+        std::fs::rename(&tmp, &path).context(anyhow!("failed in writing {:?}", path))?;
+        Ok(())
+    }
+}
+
+/// removes the cache of the workspace
+pub fn clean(repo: &dyn Repository) -> Result<()> {
+    let dir = repo.workspace_root().join(CACHE_DIR);
+    match std::fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).context(anyhow!("failed in removing {:?}", dir))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn cache_file(repo: &dyn Repository) -> PathBuf {
+    repo.workspace_root()
+        .join(CACHE_DIR)
+        .join(INFERENCE_FILE_NAME)
+}
+
+/// inferrers change with the version of nabs, with `workspace.json` and with the wasm modules it loads
+/// a cache from a different one can't be used
+fn fingerprint(repo: &dyn Repository) -> Result<u64> {
+    let config = repo
+        .get_content(Path::new(WORKSPACE_FILE_NAME))?
+        .unwrap_or_default();
+    let mut inferrers = format!("{}\n{}", env!("CARGO_PKG_VERSION"), config);
+    // `workspace.json` only has the path of a module, the module itself can be rebuilt in place
+    for plugin in repo.workspace_config()?.wasm_plugins {
+        let path = repo.workspace_root().join(&plugin.module);
+        let bytes = std::fs::read(&path)
+            .context(anyhow!("failed in reading wasm plugin={}", plugin.module))?;
+        inferrers.push_str(&format!("\n{}={}", plugin.module, xxh3_64(&bytes)));
+    }
+    Ok(hash_str(&inferrers))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    use crate::{
+        infer::{CargoInfer, Infer, InferResult, InferRunner},
+        types::{MockRepo, RawTarget, Repository, Target},
+        warning::WarningKind,
+    };
+
+    use super::{InferenceCache, RecordingRepo, clean, fingerprint};
+
+    /// counts the packages actually inferred
+    struct CountingInfer {
+        inner: CargoInfer,
+        count: Arc<AtomicUsize>,
+    }
+
+    impl Infer for CountingInfer {
+        fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.inner.from_raw_target(t)
+        }
+    }
+
+    /// the packages depending on `c`, along with the number of packages inferred
+    fn build(root: &Path, b_toml: &str) -> (Vec<String>, usize) {
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(
+            HashMap::from([
+                ("workspace.json".to_string(), "{}".to_string()),
+                ("a/nabs.json".to_string(), "{}".to_string()),
+                (
+                    "a/Cargo.toml".to_string(),
+                    "[dependencies]\nb = { path = \"../b\" }\n".to_string(),
+                ),
+                ("b/nabs.json".to_string(), "{}".to_string()),
+                ("b/Cargo.toml".to_string(), b_toml.to_string()),
+                ("c/nabs.json".to_string(), "{}".to_string()),
+                ("c/Cargo.toml".to_string(), "".to_string()),
+            ]),
+            root.to_path_buf(),
+        ));
+        let count = Arc::new(AtomicUsize::new(0));
+        let recording: Arc<dyn Repository> = Arc::new(RecordingRepo::new(Arc::clone(&repo)));
+        let runner = InferRunner::new(vec![Box::new(CountingInfer {
+            inner: CargoInfer::new(recording),
+            count: Arc::clone(&count),
+        })])
+        .with_cache(Arc::new(InferenceCache::load(repo).unwrap()));
+        let start = ["a", "b", "c"].map(|n| RawTarget::from_string_name(n.to_string()).unwrap());
+        let (graph, _) = runner.build_graph(start).unwrap();
+        let c = Target::from_string_name("c".to_string(), "cargo".to_string()).unwrap();
+        let rdeps = graph
+            .neighbors(&c)
+            .unwrap()
+            .iter()
+            .map(|t| t.name_as_string_ref().clone())
+            .collect();
+        (rdeps, count.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn test_cache() {
+        let root = std::env::temp_dir().join(format!("nabs-cache-test-{}", std::process::id()));
+        let (rdeps, inferred) = build(&root, "");
+        assert_eq!(inferred, 3);
+        assert!(rdeps.is_empty());
+
+        // nothing changed, nothing is inferred again
+        let (_, inferred) = build(&root, "");
+        assert_eq!(inferred, 0);
+
+        // only the package whose manifest changed is inferred again
+        let (rdeps, inferred) = build(&root, "[dependencies]\nc = { path = \"../c\" }\n");
+        assert_eq!(inferred, 1);
+        assert_eq!(rdeps, vec!["b".to_string()]);
+
+        clean(&MockRepo::new(HashMap::new(), root.clone())).unwrap();
+        assert!(!root.join(super::CACHE_DIR).exists());
+        let (_, inferred) = build(&root, "");
+        assert_eq!(inferred, 3);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_cache_not_saved() {
+        let root =
+            std::env::temp_dir().join(format!("nabs-cache-not-saved-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        // `.nabs` is a file, so the cache directory can't be created
+        std::fs::write(root.join(".nabs"), "").unwrap();
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(
+            HashMap::from([
+                ("a/nabs.json".to_string(), "{}".to_string()),
+                ("a/Cargo.toml".to_string(), "".to_string()),
+            ]),
+            root.clone(),
+        ));
+        let recording: Arc<dyn Repository> = Arc::new(RecordingRepo::new(Arc::clone(&repo)));
+        let runner = InferRunner::new(vec![Box::new(CargoInfer::new(recording))])
+            .with_cache(Arc::new(InferenceCache::load(repo).unwrap()));
+        let a = RawTarget::from_string_name("a".to_string()).unwrap();
+        let (_, targets) = runner.build_graph([a]).unwrap();
+        assert_eq!(targets.len(), 1);
+        let kinds: Vec<WarningKind> = runner.warnings().iter().map(|w| w.kind).collect();
+        assert_eq!(kinds, vec![WarningKind::CacheNotSaved]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_fingerprint_wasm_plugins() {
+        let root = std::env::temp_dir().join(format!("nabs-cache-wasm-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let repo = MockRepo::new(
+            HashMap::from([(
+                "workspace.json".to_string(),
+                r#"{"wasm_plugins": [{"module": "plugin.wasm"}]}"#.to_string(),
+            )]),
+            root.clone(),
+        );
+        std::fs::write(root.join("plugin.wasm"), "v1").unwrap();
+        let before = fingerprint(&repo).unwrap();
+        assert_eq!(fingerprint(&repo).unwrap(), before);
+        // rebuilt in place, `workspace.json` is the same
+        std::fs::write(root.join("plugin.wasm"), "v2").unwrap();
+        assert_ne!(fingerprint(&repo).unwrap(), before);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
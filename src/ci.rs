@@ -0,0 +1,359 @@
+/// CI pipelines for the targets affected by a change, one job per target
+/// every job carries the `ci` fields of its package's `nabs.json`, `gitlab` and `buildkite` override fields of those jobs
+/// ```json
+/// { "ci": { "runs_on": "ubuntu-latest", "test": "cargo test", "gitlab": { "tags": ["rust"] } } }
+/// ```
+/// jobs of a target wait for the jobs of the targets it depends on, where the CI system supports it
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::graph::{TargetGraph, TargetId};
+use crate::types::{RawTarget, Repository, Target};
+
+/// fields of a job set by nabs, these can't be set in `nabs.json`
+const RESERVED: [&str; 3] = ["name", "flavor", "path"];
+const GITLAB: &str = "gitlab";
+const BUILDKITE: &str = "buildkite";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Job {
+    /// unique for every target, `<package>:<flavor>`
+    pub name: String,
+    pub flavor: String,
+    /// the package directory, a posix path relative to the workspace root
+    pub path: String,
+    /// the `ci` fields of the package's `nabs.json`, other than `gitlab` and `buildkite`
+    #[serde(flatten)]
+    pub metadata: BTreeMap<String, Value>,
+    /// names of the jobs of the targets this one depends on directly
+    #[serde(skip)]
+    pub needs: Vec<String>,
+    /// `ci.gitlab` of the package
+    #[serde(skip)]
+    pub gitlab: Map<String, Value>,
+    /// `ci.buildkite` of the package
+    #[serde(skip)]
+    pub buildkite: Map<String, Value>,
+}
+
+/// the `ci` fields of a package's `nabs.json`
+struct PackageJobs {
+    metadata: BTreeMap<String, Value>,
+    gitlab: Map<String, Value>,
+    buildkite: Map<String, Value>,
+}
+
+/// a job for every target, in the same order
+/// a job needs the jobs of the targets in `targets` its target depends on, every target has to be in `graph`
+/// `targets` from `TargetGraph::rdeps` are enough, a dependency between two of them never goes through a target which is not affected
+pub fn jobs(repo: &dyn Repository, graph: &TargetGraph, targets: &[Target]) -> Result<Vec<Job>> {
+    let names: HashMap<TargetId, String> = targets
+        .iter()
+        .filter_map(|t| Some((graph.id(t)?, t.to_string())))
+        .collect();
+    let mut packages: HashMap<&str, PackageJobs> = HashMap::new();
+    let mut jobs = Vec::new();
+    for t in targets {
+        let package = t.name.to_string_ref().as_str();
+        if !packages.contains_key(package) {
+            let ci = repo.package_config(&RawTarget::new(t.name.clone()))?.ci;
+            packages.insert(package, package_jobs(package, ci)?);
+        }
+        let PackageJobs {
+            metadata,
+            gitlab,
+            buildkite,
+        } = &packages[package];
+        let mut needs: Vec<String> = graph
+            .id(t)
+            .map(|id| graph.parent_ids(id))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|p| names.get(&p).cloned())
+            .collect();
+        needs.sort();
+        jobs.push(Job {
+            name: t.to_string(),
+            flavor: t.flavor.clone(),
+            path: package.to_string(),
+            metadata: metadata.clone(),
+            needs,
+            gitlab: gitlab.clone(),
+            buildkite: buildkite.clone(),
+        });
+    }
+    Ok(jobs)
+}
+
+fn package_jobs(package: &str, mut ci: BTreeMap<String, Value>) -> Result<PackageJobs> {
+    if let Some(key) = RESERVED.iter().find(|k| ci.contains_key(**k)) {
+        bail!(
+            "`ci.{}` in nabs.json of package={} is set by nabs, it can't be overridden",
+            key,
+            package
+        );
+    }
+    let mut object = |key: &str| match ci.remove(key) {
+        None => Ok(Map::new()),
+        Some(Value::Object(o)) => Ok(o),
+        Some(_) => bail!(
+            "`ci.{}` in nabs.json of package={} has to be an object",
+            key,
+            package
+        ),
+    };
+    let gitlab = object(GITLAB)?;
+    let buildkite = object(BUILDKITE)?;
+    Ok(PackageJobs {
+        metadata: ci,
+        gitlab,
+        buildkite,
+    })
+}
+
+/// a `strategy.matrix` for GitHub Actions, every job is an entry of `include`
+/// ```yaml
+/// strategy:
+///   matrix: ${{ fromJSON(needs.changeset.outputs.matrix) }}
+/// runs-on: ${{ matrix.runs_on }}
+/// ```
+pub fn github_matrix(jobs: &[Job]) -> Value {
+    json!({ "include": jobs })
+}
+
+/// a GitLab child pipeline, every job is `template` with the fields of `Job::gitlab` on top
+/// `variables` has `variables(job)` added to it, and `needs` has the jobs of the dependencies
+/// GitLab rejects pipelines without jobs, so a job which only prints a message is added when `jobs` is empty
+pub fn gitlab_pipeline(jobs: &[Job], template: &BTreeMap<String, Value>) -> Value {
+    let mut pipeline = Map::new();
+    for job in jobs {
+        let mut definition = merged(template, &job.gitlab);
+        extend_object(&mut definition, "variables", variables(job));
+        extend_array(&mut definition, "needs", job.needs.iter().cloned());
+        pipeline.insert(job.name.clone(), Value::Object(definition));
+    }
+    if pipeline.is_empty() {
+        pipeline.insert(
+            "nabs:nothing-affected".to_string(),
+            json!({ "script": ["echo no target is affected"] }),
+        );
+    }
+    Value::Object(pipeline)
+}
+
+/// a Buildkite pipeline for `buildkite-agent pipeline upload`, every step is `template` with the fields of `Job::buildkite` on top
+/// `env` has `variables(job)` added to it, and `depends_on` has the steps of the dependencies
+/// the `key` of a step is set by nabs, the `label` defaults to the name of the job
+pub fn buildkite_pipeline(jobs: &[Job], template: &BTreeMap<String, Value>) -> Value {
+    let steps: Vec<Value> = jobs
+        .iter()
+        .map(|job| {
+            let mut step = merged(template, &job.buildkite);
+            step.entry("label").or_insert(json!(job.name));
+            step.insert("key".to_string(), json!(buildkite_key(&job.name)));
+            extend_object(&mut step, "env", variables(job));
+            extend_array(
+                &mut step,
+                "depends_on",
+                job.needs.iter().map(|n| buildkite_key(n)),
+            );
+            Value::Object(step)
+        })
+        .collect();
+    json!({ "steps": steps })
+}
+
+/// `NABS_NAME`, `NABS_FLAVOR`, `NABS_PATH`, along with `NABS_<FIELD>` for every string, number or bool in `Job::metadata`
+/// this lets a single template in `workspace.json` run a different command for every package
+pub fn variables(job: &Job) -> Map<String, Value> {
+    let mut variables = Map::new();
+    for (key, value) in &job.metadata {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(_) | Value::Bool(_) => value.to_string(),
+            _ => continue,
+        };
+        let key: String = key
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_uppercase(),
+                false => '_',
+            })
+            .collect();
+        variables.insert(format!("NABS_{}", key), json!(value));
+    }
+    variables.insert("NABS_NAME".to_string(), json!(job.name));
+    variables.insert("NABS_FLAVOR".to_string(), json!(job.flavor));
+    variables.insert("NABS_PATH".to_string(), json!(job.path));
+    variables
+}
+
+/// keys of buildkite steps can only have alphanumeric characters, `_`, `-` and `:`
+fn buildkite_key(name: &str) -> String {
+    name.chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect()
+}
+
+fn merged(
+    template: &BTreeMap<String, Value>,
+    overrides: &Map<String, Value>,
+) -> Map<String, Value> {
+    let mut merged: Map<String, Value> = template.clone().into_iter().collect();
+    merged.extend(overrides.clone());
+    merged
+}
+
+/// adds `fields` to the object at `key`, the ones already set are replaced
+fn extend_object(definition: &mut Map<String, Value>, key: &str, fields: Map<String, Value>) {
+    match definition.get_mut(key) {
+        Some(Value::Object(o)) => o.extend(fields),
+        _ => {
+            definition.insert(key.to_string(), Value::Object(fields));
+        }
+    }
+}
+
+/// adds `values` to the array at `key`
+fn extend_array(
+    definition: &mut Map<String, Value>,
+    key: &str,
+    values: impl Iterator<Item = String>,
+) {
+    let values = values.map(Value::String);
+    match definition.get_mut(key) {
+        Some(Value::Array(a)) => a.extend(values),
+        _ => {
+            definition.insert(key.to_string(), Value::Array(values.collect()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use anyhow::Result;
+    use serde_json::json;
+
+    use super::{buildkite_pipeline, github_matrix, gitlab_pipeline, jobs};
+    use crate::graph::{EdgeKind, TargetGraph};
+    use crate::types::{MockRepo, Target};
+
+    fn repo() -> MockRepo {
+        let fake: HashMap<String, String> = [
+            (
+                "libs/core/nabs.json",
+                r#"{ "ci": { "runs_on": "ubuntu-latest", "test": "cargo test", "gitlab": { "tags": ["rust"] } } }"#,
+            ),
+            ("apps/web/nabs.json", ""),
+            ("apps/bad/nabs.json", r#"{ "ci": { "path": "elsewhere" } }"#),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        MockRepo::new(fake, PathBuf::new())
+    }
+
+    fn target(name: &str, flavor: &str) -> Target {
+        Target::from_string_name(name.to_string(), flavor.to_string()).unwrap()
+    }
+
+    /// libs/core:cargo -> apps/web:cargo, libs/core:python_requirements on its own
+    fn graph() -> (TargetGraph, Vec<Target>) {
+        let targets = vec![
+            target("libs/core", "cargo"),
+            target("libs/core", "python_requirements"),
+            target("apps/web", "cargo"),
+        ];
+        let mut g = TargetGraph::new();
+        for t in &targets {
+            g.add_node(t.clone());
+        }
+        g.add_edge(&targets[0], &targets[2], EdgeKind::Declared)
+            .unwrap();
+        (g, targets)
+    }
+
+    #[test]
+    fn test_github_matrix() -> Result<()> {
+        let repo = repo();
+        let (g, targets) = graph();
+        let matrix = github_matrix(&jobs(&repo, &g, &targets)?);
+        assert_eq!(
+            matrix,
+            json!({ "include": [
+                {
+                    "name": "libs/core:cargo",
+                    "flavor": "cargo",
+                    "path": "libs/core",
+                    "runs_on": "ubuntu-latest",
+                    "test": "cargo test",
+                },
+                {
+                    "name": "libs/core:python_requirements",
+                    "flavor": "python_requirements",
+                    "path": "libs/core",
+                    "runs_on": "ubuntu-latest",
+                    "test": "cargo test",
+                },
+                { "name": "apps/web:cargo", "flavor": "cargo", "path": "apps/web" },
+            ]})
+        );
+        assert_eq!(github_matrix(&[]), json!({ "include": [] }));
+        assert!(jobs(&repo, &g, &[target("apps/bad", "cargo")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pipelines() -> Result<()> {
+        let (g, targets) = graph();
+        // only the web app is affected, the job of core is not there to wait for
+        let jobs_web = jobs(&repo(), &g, &targets[2..])?;
+        assert!(jobs_web[0].needs.is_empty());
+        let jobs = jobs(&repo(), &g, &targets)?;
+        assert_eq!(jobs[2].needs, vec!["libs/core:cargo"]);
+
+        let template = [("script".to_string(), json!(["cd $NABS_PATH && $NABS_TEST"]))]
+            .into_iter()
+            .collect();
+        let gitlab = gitlab_pipeline(&jobs, &template);
+        assert_eq!(
+            gitlab["libs/core:cargo"],
+            json!({
+                "script": ["cd $NABS_PATH && $NABS_TEST"],
+                "tags": ["rust"],
+                "needs": [],
+                "variables": {
+                    "NABS_NAME": "libs/core:cargo",
+                    "NABS_FLAVOR": "cargo",
+                    "NABS_PATH": "libs/core",
+                    "NABS_RUNS_ON": "ubuntu-latest",
+                    "NABS_TEST": "cargo test",
+                },
+            })
+        );
+        assert_eq!(
+            gitlab["apps/web:cargo"]["needs"],
+            json!(["libs/core:cargo"])
+        );
+        assert!(gitlab_pipeline(&[], &template)["nabs:nothing-affected"].is_object());
+
+        let buildkite = buildkite_pipeline(&jobs, &template);
+        let web = &buildkite["steps"][2];
+        assert_eq!(web["key"], json!("apps_web:cargo"));
+        assert_eq!(web["label"], json!("apps/web:cargo"));
+        assert_eq!(web["depends_on"], json!(["libs_core:cargo"]));
+        assert_eq!(web["env"]["NABS_PATH"], json!("apps/web"));
+        assert!(web.get("tags").is_none());
+        Ok(())
+    }
+}
@@ -1,26 +1,146 @@
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
 use anyhow::Result;
+use cache::CacheCommands;
 use changeset::get_changeset;
+use check::run_checks;
+use ci::CiCommands;
+use daemon::run_daemon;
+use deps::print_deps;
+use exec::run_exec;
 use graph::print_graph;
+use nabs::{Diagnostics, Monorepo, Workspace, query::Snapshot};
+use serve::run_server;
+use why::print_why;
 
+mod cache;
 mod changeset;
+mod check;
+mod ci;
+mod daemon;
+mod deps;
+mod exec;
 mod graph;
-
+mod serve;
+mod why;
 
 #[derive(clap::Subcommand)]
 pub enum Commands {
-    Changeset,
+    Changeset {
+        /// only infer the packages which could depend on the changed ones, found by scanning manifests for paths
+        #[arg(long)]
+        lazy: bool,
+        /// ask the running `nabs daemon` instead of building the graph
+        #[arg(long, conflicts_with = "lazy")]
+        daemon: bool,
+    },
     Graph,
+    /// every package the given packages depend on, transitively
+    Deps {
+        #[arg(required = true)]
+        packages: Vec<String>,
+        /// ask the running `nabs daemon` instead of building the graph
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// the chain of dependencies through which a change in package `from` affects package `to`
+    Why {
+        from: String,
+        to: String,
+        /// ask the running `nabs daemon` instead of building the graph
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// run a command in the directory of every package, a package only starts once the packages it depends on succeeded
+    Exec {
+        /// only the packages affected by the changed files on stdin, like `changeset`
+        #[arg(long)]
+        changeset: bool,
+        /// same as `changeset --lazy`
+        #[arg(long, requires = "changeset")]
+        lazy: bool,
+        /// how many commands run at the same time, defaults to the number of CPUs
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// keep running the packages which don't depend on a failed one, instead of stopping at the first failure
+        #[arg(long)]
+        keep_going: bool,
+        /// the program and its arguments, `NABS_PACKAGE` is set to the package name
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// keep the graph in memory, updating it as manifests change, and answer `--daemon` queries
+    Daemon,
+    /// answer graph queries from editors using JSON-RPC, see `nabs::rpc`
+    Serve {
+        /// talk over stdin and stdout, this is the only transport right now
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+    /// run consistency checks on the manifests of all packages
+    Check,
+    /// CI pipelines for the affected targets
+    Ci {
+        #[command(subcommand)]
+        command: CiCommands,
+    },
+    /// manage the inference cache in `.nabs/cache`
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+}
+
+/// flags shared by every command
+pub struct Options {
+    pub strict: bool,
+    pub cache: bool,
+    pub diagnostics: Arc<Diagnostics>,
+}
+
+impl Options {
+    /// the workspace enclosing the current directory, reporting warnings to our diagnostics
+    fn workspace(&self) -> Result<Workspace> {
+        let repo = Monorepo::new()?.with_diagnostics(Arc::clone(&self.diagnostics));
+        Ok(Workspace::from_repo(Arc::new(repo))?
+            .with_diagnostics(Arc::clone(&self.diagnostics))
+            .with_cache(self.cache))
+    }
+
+    /// the graph of every package in `workspace`, its warnings are already reported
+    fn snapshot(&self, workspace: &Workspace) -> Result<Snapshot> {
+        let (graph, _) = workspace.build_graph()?;
+        if self.strict {
+            workspace.deny_warnings(&self.diagnostics.warnings())?;
+        }
+        let packages: HashSet<PathBuf> = HashSet::from_iter(workspace.repo().get_nabs_packages());
+        Ok(Snapshot::new(graph, packages, Vec::new()))
+    }
 }
 
-pub fn run_command(command: Option<Commands>) -> Result<()> {
+pub fn run_command(command: Option<Commands>, options: &Options) -> Result<()> {
     match command {
         None => {
             eprintln!("empty command not allowed");
             Ok(())
         }
         Some(c) => match c {
-            Commands::Changeset => get_changeset(),
-            Commands::Graph => print_graph(),
+            Commands::Changeset { lazy, daemon } => get_changeset(options, lazy, daemon),
+            Commands::Graph => print_graph(options),
+            Commands::Deps { packages, daemon } => print_deps(options, packages, daemon),
+            Commands::Why { from, to, daemon } => print_why(options, from, to, daemon),
+            Commands::Exec {
+                changeset,
+                lazy,
+                jobs,
+                keep_going,
+                command,
+            } => run_exec(options, command, changeset, lazy, jobs, keep_going),
+            Commands::Daemon => run_daemon(options),
+            Commands::Serve { stdio: _ } => run_server(options),
+            Commands::Check => run_checks(options),
+            Commands::Ci { command } => ci::run(command, options),
+            Commands::Cache { command } => cache::run(command),
         },
     }
 }
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use nabs::{Workspace, cache::clean};
+
+#[derive(clap::Subcommand)]
+pub enum CacheCommands {
+    /// remove the cache, the next run infers every package again
+    Clean,
+}
+
+pub fn run(command: CacheCommands) -> Result<()> {
+    let workspace = Workspace::open()?;
+    match command {
+        CacheCommands::Clean => clean(workspace.repo().as_ref()),
+    }
+}
@@ -1,59 +1,91 @@
-use std::{collections::HashSet, io::Read, path::PathBuf, rc::Rc};
+use std::{collections::HashSet, io::Read, path::PathBuf};
 
-use crate::graph::TargetGraph;
-use crate::infer::InferRunner;
-use crate::types::{Monorepo, RawTarget, Repository, Target, TargetName};
-use anyhow::{Context, Result, anyhow};
-use log::{debug, info};
+use anyhow::Result;
+use log::info;
+use nabs::{
+    Diagnostics, RawTarget, Target, TargetGraph, TargetName, Workspace,
+    query::{Request, changed_packages, missing_targets},
+};
 
-pub fn get_changeset() -> Result<()> {
+use super::{Options, daemon::ask};
+
+pub fn get_changeset(options: &Options, lazy: bool, daemon: bool) -> Result<()> {
     let files_to_find_diff = get_input()?;
 
-    let monorepo: Rc<dyn Repository> = Rc::new(Monorepo::new()?);
-    let pkgs: HashSet<PathBuf> = HashSet::from_iter(monorepo.get_nabs_packages());
+    let workspace = options.workspace()?;
+    if daemon {
+        let request = Request::Changeset {
+            files: files_to_find_diff,
+        };
+        for name in ask(options, &workspace, &request)? {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+    let (_, affected) = affected_targets(options, &workspace, &files_to_find_diff, lazy)?;
+    for target in affected {
+        println!("{}", target.name_as_string_ref());
+    }
+    Ok(())
+}
+
+/// every target affected by a change in `files`, along with the graph they are in
+/// fails on warnings in strict mode
+pub fn affected_targets(
+    options: &Options,
+    workspace: &Workspace,
+    files_to_find_diff: &[PathBuf],
+    lazy: bool,
+) -> Result<(TargetGraph, Vec<Target>)> {
+    let pkgs: HashSet<PathBuf> = HashSet::from_iter(workspace.repo().get_nabs_packages());
     info!("all detected packages, {:?}", pkgs);
 
-    let to_search = get_pkgs_to_search(&files_to_find_diff, &pkgs)?;
+    let (to_search, warnings) = changed_packages(files_to_find_diff, &pkgs)?;
+    for w in warnings {
+        options.diagnostics.warn(w);
+    }
     info!("changed packages: {:?}", to_search);
 
-    let runner = InferRunner::default(&monorepo);
-    let targets = to_raw_targets(&pkgs)?;
-    let (graph, our_targets) = build_graph_and_our_targets(&runner, targets, &to_search)?;
+    let (graph, our_targets) = if lazy {
+        let changed: Vec<RawTarget> = to_search.iter().cloned().map(RawTarget::new).collect();
+        our_targets(
+            workspace.build_rdeps_graph(&changed)?,
+            &to_search,
+            &options.diagnostics,
+        )
+    } else {
+        let runner = workspace.runner()?;
+        let targets = workspace.packages()?;
+        our_targets(
+            runner.build_graph(targets)?,
+            &to_search,
+            &options.diagnostics,
+        )
+    };
     let result = graph.rdeps(&our_targets)?;
-    for target in result {
-        println!("{}", target.name_as_string_ref());
+    if options.strict {
+        workspace.deny_warnings(&options.diagnostics.warnings())?;
     }
-    Ok(())
+    Ok((graph, result))
 }
 
-fn build_graph_and_our_targets(
-    runner: &InferRunner,
-    targets: Vec<RawTarget>,
+/// the targets of the changed packages, a changed package without one is reported to `diagnostics`
+fn our_targets(
+    (graph, targets): (TargetGraph, Vec<Target>),
     to_search: &HashSet<TargetName>,
-) -> Result<(TargetGraph, Vec<Target>)> {
-    let (graph, our_targets) = runner.build_graph(targets)?;
-    let our_targets: Vec<Target> = our_targets
+    diagnostics: &Diagnostics,
+) -> (TargetGraph, Vec<Target>) {
+    let our_targets: Vec<Target> = targets
         .into_iter()
         .filter(|t| to_search.contains(&t.name))
         .collect();
-    validate_and_warn_on_missing_targets(&our_targets, to_search);
-    Ok((graph, our_targets))
-}
-
-fn to_raw_targets(pkgs: &HashSet<PathBuf>) -> Result<Vec<RawTarget>> {
-    let mut targets = Vec::new();
-    for p in pkgs {
-        let val = p
-            .to_str()
-            .ok_or(anyhow!("could not parse path: {:?}", p))?
-            .to_string();
-        let val = RawTarget::from_string_name(val)?;
-        targets.push(val);
+    for w in missing_targets(to_search, &our_targets) {
+        diagnostics.warn(w);
     }
-    Ok(targets)
+    (graph, our_targets)
 }
 
-fn get_input() -> Result<Vec<PathBuf>> {
+pub fn get_input() -> Result<Vec<PathBuf>> {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input)?;
     let files_to_find_diff: Vec<PathBuf> = input
@@ -63,53 +95,3 @@ fn get_input() -> Result<Vec<PathBuf>> {
         .collect();
     Ok(files_to_find_diff)
 }
-
-fn validate_and_warn_on_missing_targets(
-    our_targets: &Vec<Target>,
-    to_search: &HashSet<TargetName>,
-) {
-    if our_targets.len() != to_search.len() {
-        let ts: HashSet<&TargetName> = our_targets.iter().map(|v| &v.name).collect();
-        for t in to_search {
-            if !ts.contains(t) {
-                eprintln!("warn: file={} not part of any package", t);
-            }
-        }
-    }
-}
-
-fn get_pkgs_to_search(
-    files_to_find_diff: &Vec<PathBuf>,
-    pkgs: &HashSet<PathBuf>,
-) -> Result<HashSet<TargetName>> {
-    let mut pkgs_to_search = HashSet::new();
-    for f in files_to_find_diff {
-        let pkg = which_pkg(&f, &pkgs);
-        match pkg {
-            None => {
-                eprintln!("file={} is not part of any package", f.to_string_lossy());
-            }
-            Some(v) => {
-                let v_str = v
-                    .to_str()
-                    .ok_or(anyhow!("could not parse package path: {:?}", v))?;
-                pkgs_to_search.insert(
-                    TargetName::new(v_str.to_string())
-                        .context(anyhow!("failed to create target for {:?}", v))?,
-                );
-            }
-        }
-    }
-    Ok(pkgs_to_search)
-}
-
-fn which_pkg(p: &PathBuf, pkgs: &HashSet<PathBuf>) -> Option<PathBuf> {
-    let mut cur = Some(p.clone());
-    while let Some(p) = cur {
-        if pkgs.contains(&p) {
-            return Some(p.clone());
-        }
-        cur = p.parent().map(|v| PathBuf::from(v));
-    }
-    None
-}
@@ -0,0 +1,56 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, bail};
+use nabs::{Diagnostics, RawTarget, Repository, Warning, infer::CargoInfer};
+
+use super::Options;
+
+/// runs every check on every package in the workspace, each problem is reported as a warning
+/// fails if any problem is found
+pub fn run_checks(options: &Options) -> Result<()> {
+    let workspace = options.workspace()?;
+    let pkgs = workspace.packages()?;
+
+    let n_problems = check_cargo_usage(workspace.repo(), &pkgs, &options.diagnostics)?;
+    if options.strict {
+        workspace.deny_warnings(&options.diagnostics.warnings())?;
+    }
+    if n_problems > 0 {
+        bail!("check failed, found {} problems", n_problems);
+    }
+    Ok(())
+}
+
+/// cross checks path dependencies in `Cargo.toml` with the crates used in code
+/// returns the number of problems found
+fn check_cargo_usage(
+    repo: &Arc<dyn Repository>,
+    pkgs: &[RawTarget],
+    diagnostics: &Diagnostics,
+) -> Result<usize> {
+    let cargo = CargoInfer::new(Arc::clone(repo));
+    let mut crates = HashMap::new();
+    for p in pkgs {
+        if let Some(name) = cargo.crate_name(p)? {
+            crates.insert(name, p.clone());
+        }
+    }
+
+    let mut n_problems = 0;
+    for p in pkgs {
+        let Some(problems) = cargo.check_usage(p, &crates)? else {
+            continue;
+        };
+        for (problem, location) in problems {
+            diagnostics.warn(Warning {
+                kind: problem.kind(),
+                package: p.to_string(),
+                subject: problem.subject().to_string(),
+                reason: problem.to_string(),
+                location: Some(location),
+            });
+            n_problems += 1;
+        }
+    }
+    Ok(n_problems)
+}
@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use nabs::{
+    Workspace,
+    ci::{Job, buildkite_pipeline, github_matrix, gitlab_pipeline, jobs},
+};
+
+use super::{
+    Options,
+    changeset::{affected_targets, get_input},
+};
+
+#[derive(clap::Subcommand)]
+pub enum CiCommands {
+    /// a `strategy.matrix` for GitHub Actions with a job for every affected target, reads changed files like `changeset`
+    GithubMatrix {
+        /// print a matrix without any job when nothing is affected, instead of failing
+        /// GitHub Actions rejects empty matrices, skip the job using `if: fromJSON(...).include[0]`
+        #[arg(long)]
+        allow_empty: bool,
+        /// same as `changeset --lazy`
+        #[arg(long)]
+        lazy: bool,
+    },
+    /// a GitLab child pipeline with a job for every affected target, jobs `needs` the jobs of their dependencies
+    Gitlab {
+        /// write the pipeline to this file instead of stdout, for the artifact of a `trigger` job
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// write a pipeline with a single job printing a message when nothing is affected, instead of failing
+        #[arg(long)]
+        allow_empty: bool,
+        /// same as `changeset --lazy`
+        #[arg(long)]
+        lazy: bool,
+    },
+    /// a Buildkite pipeline for `buildkite-agent pipeline upload` with a step for every affected target, steps `depends_on` the steps of their dependencies
+    Buildkite {
+        /// print a pipeline without any step when nothing is affected, instead of failing
+        #[arg(long)]
+        allow_empty: bool,
+        /// same as `changeset --lazy`
+        #[arg(long)]
+        lazy: bool,
+    },
+}
+
+pub fn run(command: CiCommands, options: &Options) -> Result<()> {
+    let workspace = options.workspace()?;
+    match command {
+        CiCommands::GithubMatrix { allow_empty, lazy } => {
+            let jobs = affected_jobs(options, &workspace, lazy, allow_empty)?;
+            // on one line, ready for `echo "matrix=..." >> $GITHUB_OUTPUT`
+            println!("{}", serde_json::to_string(&github_matrix(&jobs))?);
+        }
+        CiCommands::Gitlab {
+            output,
+            allow_empty,
+            lazy,
+        } => {
+            let jobs = affected_jobs(options, &workspace, lazy, allow_empty)?;
+            let pipeline = gitlab_pipeline(&jobs, &workspace.config().ci.gitlab);
+            let yaml = serde_yaml::to_string(&pipeline)?;
+            match output {
+                Some(path) => std::fs::write(path, yaml)?,
+                None => print!("{}", yaml),
+            }
+        }
+        CiCommands::Buildkite { allow_empty, lazy } => {
+            let jobs = affected_jobs(options, &workspace, lazy, allow_empty)?;
+            let pipeline = buildkite_pipeline(&jobs, &workspace.config().ci.buildkite);
+            print!("{}", serde_yaml::to_string(&pipeline)?);
+        }
+    }
+    Ok(())
+}
+
+/// a job for every target affected by the files on stdin
+fn affected_jobs(
+    options: &Options,
+    workspace: &Workspace,
+    lazy: bool,
+    allow_empty: bool,
+) -> Result<Vec<Job>> {
+    let files = get_input()?;
+    let (graph, targets) = affected_targets(options, workspace, &files, lazy)?;
+    if targets.is_empty() && !allow_empty {
+        bail!("no target is affected, pass --allow-empty to generate a pipeline anyway");
+    }
+    jobs(workspace.repo().as_ref(), &graph, &targets)
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use nabs::{Workspace, query::Request};
+
+use super::Options;
+
+#[cfg(unix)]
+pub fn run_daemon(options: &Options) -> Result<()> {
+    let workspace = options.workspace()?;
+    nabs::daemon::run(std::sync::Arc::clone(workspace.repo()), options.cache)
+}
+
+#[cfg(not(unix))]
+pub fn run_daemon(_options: &Options) -> Result<()> {
+    anyhow::bail!("`nabs daemon` is only supported on unix")
+}
+
+/// answers `request` using the daemon running for `workspace`
+/// its warnings are reported as if this process found them, so `--strict` works the same way
+pub fn ask(options: &Options, workspace: &Workspace, request: &Request) -> Result<Vec<String>> {
+    let response = request_daemon(workspace, request)?;
+    for w in response.warnings {
+        options.diagnostics.warn(w);
+    }
+    if options.strict {
+        workspace.deny_warnings(&options.diagnostics.warnings())?;
+    }
+    Ok(response.packages)
+}
+
+#[cfg(unix)]
+fn request_daemon(workspace: &Workspace, request: &Request) -> Result<nabs::query::Response> {
+    nabs::daemon::request(workspace.repo().workspace_root(), request)
+}
+
+#[cfg(not(unix))]
+fn request_daemon(_workspace: &Workspace, _request: &Request) -> Result<nabs::query::Response> {
+    anyhow::bail!("`--daemon` is only supported on unix")
+}
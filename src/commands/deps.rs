@@ -0,0 +1,26 @@
+use anyhow::Result;
+use nabs::{TargetName, query::Request};
+
+use super::{Options, daemon::ask};
+
+pub fn print_deps(options: &Options, packages: Vec<String>, daemon: bool) -> Result<()> {
+    let workspace = options.workspace()?;
+    let deps = if daemon {
+        ask(options, &workspace, &Request::Deps { packages })?
+    } else {
+        let snapshot = options.snapshot(&workspace)?;
+        let names = packages
+            .into_iter()
+            .map(TargetName::new)
+            .collect::<Result<Vec<_>>>()?;
+        snapshot
+            .deps(&names)?
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect()
+    };
+    for name in deps {
+        println!("{}", name);
+    }
+    Ok(())
+}
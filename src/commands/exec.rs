@@ -0,0 +1,40 @@
+use anyhow::{Result, bail};
+use nabs::exec::{ExecOptions, Status, run, summary, tasks};
+
+use super::{
+    Options,
+    changeset::{affected_targets, get_input},
+};
+
+pub fn run_exec(
+    options: &Options,
+    command: Vec<String>,
+    changeset: bool,
+    lazy: bool,
+    jobs: Option<usize>,
+    keep_going: bool,
+) -> Result<()> {
+    let workspace = options.workspace()?;
+    let (graph, targets) = if changeset {
+        affected_targets(options, &workspace, &get_input()?, lazy)?
+    } else {
+        let built = workspace.build_graph()?;
+        if options.strict {
+            workspace.deny_warnings(&options.diagnostics.warnings())?;
+        }
+        built
+    };
+    let tasks = tasks(workspace.repo().as_ref(), &graph, &targets)?;
+    let jobs = jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let outcomes = run(&tasks, &command, &ExecOptions { jobs, keep_going });
+
+    eprint!("\n{}", summary(&outcomes));
+    let failed = outcomes
+        .iter()
+        .filter(|o| matches!(o.status, Status::Failed(_)))
+        .count();
+    if failed > 0 {
+        bail!("{} of {} packages failed", failed, outcomes.len());
+    }
+    Ok(())
+}
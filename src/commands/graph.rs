@@ -1,35 +1,15 @@
-use std::{collections::HashSet, path::PathBuf, rc::Rc};
+use anyhow::Result;
 
-use anyhow::{anyhow, Result};
-use log::{debug, info};
+use super::Options;
 
-use crate::{infer::InferRunner, types::{Monorepo, RawTarget, Repository}};
-
-pub fn print_graph() -> Result<()> {
-    let monorepo: Rc<dyn Repository> = Rc::new(Monorepo::new()?);
-    let pkgs: HashSet<PathBuf> = HashSet::from_iter(monorepo.get_nabs_packages());
-    info!("all detected packages, {:?}", monorepo.get_nabs_packages());
-
-    let runner = InferRunner::default(&monorepo);
-    let targets = to_raw_targets(&pkgs)?;
-    debug!("created raw targets {:?}", targets);
-    let (graph, _) = runner.build_graph(targets)?;
+pub fn print_graph(options: &Options) -> Result<()> {
+    let workspace = options.workspace()?;
+    let runner = workspace.runner()?;
+    let (graph, _) = runner.build_graph(workspace.packages()?)?;
+    if options.strict {
+        workspace.deny_warnings(&options.diagnostics.warnings())?;
+    }
 
     println!("graph:\n{}", graph);
     Ok(())
 }
-
-
-// todo: remove this duplication, same code inside changeset also
-fn to_raw_targets(pkgs: &HashSet<PathBuf>) -> Result<Vec<RawTarget>> {
-    let mut targets = Vec::new();
-    for p in pkgs {
-        let val = p
-            .to_str()
-            .ok_or(anyhow!("could not parse path: {:?}", p))?
-            .to_string();
-        let val = RawTarget::from_string_name(val)?;
-        targets.push(val);
-    }
-    Ok(targets)
-}
\ No newline at end of file
@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use nabs::rpc::serve;
+
+use super::Options;
+
+pub fn run_server(options: &Options) -> Result<()> {
+    let workspace = options.workspace()?;
+    serve(
+        Arc::clone(workspace.repo()),
+        options.cache,
+        std::io::stdin().lock(),
+        std::io::stdout(),
+    )
+}
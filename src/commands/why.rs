@@ -0,0 +1,20 @@
+use anyhow::Result;
+use nabs::{TargetName, query::Request};
+
+use super::{Options, daemon::ask};
+
+pub fn print_why(options: &Options, from: String, to: String, daemon: bool) -> Result<()> {
+    let workspace = options.workspace()?;
+    let path = if daemon {
+        ask(options, &workspace, &Request::Why { from, to })?
+    } else {
+        let snapshot = options.snapshot(&workspace)?;
+        snapshot
+            .why(&TargetName::new(from)?, &TargetName::new(to)?)?
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect()
+    };
+    println!("{}", path.join(" -> "));
+    Ok(())
+}
@@ -0,0 +1,178 @@
+/// configuration files read by nabs
+/// `workspace.json` lives at the root of the monorepo and holds workspace wide settings
+/// `nabs.json` lives in every package and holds settings for that package
+/// both files are allowed to be empty objects (`{}`), every field has a default
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::error::NabsError;
+use crate::types::PathFormat;
+
+pub const WORKSPACE_FILE_NAME: &str = "workspace.json";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WorkspaceConfig {
+    /// built-in inferrers which are off by default
+    #[serde(default)]
+    pub enable: EnableConfig,
+    #[serde(default)]
+    pub proto: ProtoConfig,
+    #[serde(default)]
+    pub python: PythonConfig,
+    /// inferrers for build systems nabs does not support natively
+    #[serde(default)]
+    pub inferrers: Vec<InferrerConfig>,
+    /// inferrers implemented as external executables, see `infer::plugin` for the protocol
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    /// inferrers compiled to WebAssembly, see `infer::wasm` for the interface
+    #[serde(default)]
+    pub wasm_plugins: Vec<WasmPluginConfig>,
+    /// known warnings, these don't fail strict mode
+    #[serde(default)]
+    pub allow_warnings: Vec<AllowedWarning>,
+    /// the jobs `nabs ci` generates for every affected target
+    #[serde(default)]
+    pub ci: CiConfig,
+}
+
+/// a job template for every CI system, packages can override its fields in the `ci` of their `nabs.json`
+/// ```json
+/// { "gitlab": { "image": "rust:1", "script": ["cd $NABS_PATH && $NABS_TEST"] } }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct CiConfig {
+    /// fields of every GitLab job
+    #[serde(default)]
+    pub gitlab: BTreeMap<String, serde_json::Value>,
+    /// fields of every Buildkite step
+    #[serde(default)]
+    pub buildkite: BTreeMap<String, serde_json::Value>,
+}
+
+/// a warning which is allowed in strict mode, fields which are not set match anything
+/// ```json
+/// { "code": "failed-parent", "package": "legacy/*", "subject": "${ROOT}/vendor" }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct AllowedWarning {
+    /// the warning code, like `failed-parent`
+    pub code: String,
+    /// glob for the package name
+    #[serde(default)]
+    pub package: Option<String>,
+    /// the dependency or file the warning is about
+    #[serde(default)]
+    pub subject: Option<String>,
+}
+
+/// an external inferrer, called once for every package
+/// ```json
+/// { "command": "./tools/nabs-make.py", "args": ["--strict"] }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginConfig {
+    /// the executable, a path containing `/` is relative to the workspace root, otherwise it is looked up in `PATH`
+    pub command: String,
+    /// arguments passed before the package directory
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// a WebAssembly inferrer, it runs sandboxed and can only read files inside the workspace
+/// ```json
+/// { "module": "tools/nabs-gradle.wasm" }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct WasmPluginConfig {
+    /// path to the module, relative to the workspace root
+    pub module: String,
+}
+
+/// a regex based inferrer
+/// ```json
+/// {
+///     "flavor": "go",
+///     "manifest": "go.mod",
+///     "pattern": "=>\\s*(\\.\\.?/\\S+)",
+///     "path_format": "posix"
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct InferrerConfig {
+    /// the flavor of every target detected by this inferrer
+    pub flavor: String,
+    /// glob for the manifest files, relative to the package directory, like `*.csproj` or `build.gradle`
+    pub manifest: String,
+    /// regex run on the content of the manifests, every match is a dependency
+    /// the path is taken from the capture group named `path`, or the first capture group if there is no such group
+    pub pattern: String,
+    /// the format of the captured paths, `posix` or `host`
+    #[serde(default)]
+    pub path_format: PathFormat,
+}
+
+/// built-in inferrers for build files which often sit next to the manifest of another build system
+/// a package detected by two inferrers fails with `AmbiguousInference`, so these have to be turned on
+/// ```json
+/// { "enable": { "cmake": true } }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct EnableConfig {
+    /// `CMakeLists.txt` and `conanws.yml`, crates and python packages often vendor a `CMakeLists.txt` for native code
+    #[serde(default)]
+    pub cmake: bool,
+    /// `BUILD`, `BUILD.bazel` and `MODULE.bazel`, a package being migrated to bazel has these next to its old manifest
+    #[serde(default)]
+    pub bazel: bool,
+    /// `Gemfile`, ruby tooling (like fastlane in a mobile app) brings one into packages of other languages
+    #[serde(default)]
+    pub gemfile: bool,
+    /// `flake.nix` and `default.nix`, dev shells and packaging often live in the package they build
+    #[serde(default)]
+    pub nix: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PythonConfig {
+    /// scan `import` statements in `.py` files of every package and link them to the workspace packages providing those modules
+    /// this finds dependencies which are never declared in `requirements.txt` (like the ones added using `PYTHONPATH`)
+    #[serde(default)]
+    pub scan_imports: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProtoConfig {
+    /// directories (posix paths relative to the workspace root) that `import` statements are resolved against
+    /// this is the equivalent of `protoc -I`, proto inference is disabled if this is empty
+    #[serde(default)]
+    pub include_roots: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PackageConfig {
+    /// proto packages (posix paths relative to this package) whose generated code this package uses
+    #[serde(default)]
+    pub proto_sources: Vec<String>,
+    /// top level python modules this package provides, used when `python.scan_imports` is enabled
+    /// if not set, these are detected from the directories containing `__init__.py` (in the package root or in `src/`)
+    #[serde(default)]
+    pub python_modules: Option<Vec<String>>,
+    /// extra fields for the CI jobs of this package, like a runner label or the test command
+    /// `nabs ci` copies them into the job of every target of the package, `gitlab` and `buildkite` override fields of those jobs
+    #[serde(default)]
+    pub ci: BTreeMap<String, serde_json::Value>,
+}
+
+/// parse the content of a config file, an empty file is treated as `{}`
+pub fn parse_config<T>(content: &str, file: &str) -> Result<T>
+where
+    T: Default + for<'de> Deserialize<'de>,
+{
+    if content.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(content).map_err(|e| NabsError::from_json(PathBuf::from(file), &e).into())
+}
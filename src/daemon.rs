@@ -0,0 +1,97 @@
+/// `nabs daemon` keeps a `LiveGraph` of the workspace in memory and answers queries over a unix socket in `.nabs`
+///
+/// the protocol is one JSON `query::Request` per line, answered by one JSON `query::Response` per line
+/// ```json
+/// {"method": "changeset", "files": ["libs/core/src/lib.rs"]}
+/// {"method": "deps", "packages": ["apps/web"]}
+/// {"method": "why", "from": "libs/core", "to": "apps/web"}
+/// ```
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::info;
+
+use crate::live::LiveGraph;
+use crate::query::{Request, Response};
+use crate::types::Repository;
+
+/// relative to the workspace root
+pub const SOCKET_FILE: &str = ".nabs/daemon.sock";
+
+/// sends a request to the daemon running for the workspace at `root`
+/// fails if no daemon is running, or if the daemon could not answer
+pub fn request(root: &Path, request: &Request) -> Result<Response> {
+    let path = root.join(SOCKET_FILE);
+    let mut stream = UnixStream::connect(&path).context(anyhow!(
+        "no daemon is running for this workspace, start one with `nabs daemon` (socket={})",
+        path.display()
+    ))?;
+    serde_json::to_writer(&mut stream, request)?;
+    stream.write_all(b"\n")?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response: Response =
+        serde_json::from_str(&line).context("invalid response from the daemon")?;
+    if let Some(e) = response.error {
+        bail!("daemon failed in answering, {}", e);
+    }
+    Ok(response)
+}
+
+/// runs the daemon for the workspace in `repo` until the process is stopped
+pub fn run(repo: Arc<dyn Repository>, cache: bool) -> Result<()> {
+    let socket = repo.workspace_root().join(SOCKET_FILE);
+    if UnixStream::connect(&socket).is_ok() {
+        bail!(
+            "a daemon is already running for this workspace, socket={}",
+            socket.display()
+        );
+    }
+    // left behind by a daemon which did not stop cleanly
+    let _ = std::fs::remove_file(&socket);
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let graph = Arc::new(LiveGraph::start(repo, cache, |_| {})?);
+    let listener = UnixListener::bind(&socket)
+        .context(anyhow!("failed in creating socket={}", socket.display()))?;
+
+    eprintln!("nabs daemon listening on {}", socket.display());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let graph = Arc::clone(&graph);
+        std::thread::spawn(move || {
+            if let Err(e) = serve(stream, &graph) {
+                info!("connection closed, {:#}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// answers every request sent on one connection
+fn serve(stream: UnixStream, graph: &LiveGraph) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => graph.snapshot().answer(request),
+            Err(e) => Response {
+                error: Some(format!("invalid request, {}", e)),
+                ..Default::default()
+            },
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,364 @@
+/// diagnostics are the warnings found during a run, along with the error the run failed with (if any)
+/// a `Diagnostics` sink is shared by everything which reports warnings, like `InferRunner` and the cli commands
+/// in text format warnings are printed as they happen, JSON and SARIF (for code scanning tools) are rendered once the run is over
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::{NabsError, line_of_offset};
+use crate::warning::Warning;
+
+/// code of errors which are not a `NabsError`
+pub const GENERIC_ERROR_CODE: &str = "error";
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosticsFormat {
+    #[default]
+    Text,
+    Json,
+    Sarif,
+}
+
+impl FromStr for DiagnosticsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(DiagnosticsFormat::Text),
+            "json" => Ok(DiagnosticsFormat::Json),
+            "sarif" => Ok(DiagnosticsFormat::Sarif),
+            _ => Err(anyhow!(
+                "unknown diagnostics format={}, expected one of text, json, sarif",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// a place in a file of the workspace, `file` is relative to the workspace root
+/// `line` and `column` are 1 based, they are only set when the inferrer knows them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub file: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl Location {
+    pub fn file(file: &Path) -> Self {
+        Location {
+            file: file.to_path_buf(),
+            line: None,
+            column: None,
+        }
+    }
+
+    /// the first place `needle` is mentioned in `content`, only the file if it is not mentioned at all
+    pub fn find(file: &Path, content: &str, needle: &str) -> Self {
+        match content.find(needle).filter(|_| !needle.is_empty()) {
+            None => Location::file(file),
+            Some(offset) => Location::at(file, content, offset),
+        }
+    }
+
+    /// the line and column of the byte `offset` in `content`
+    pub fn at(file: &Path, content: &str, offset: usize) -> Self {
+        let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            file: file.to_path_buf(),
+            line: Some(line_of_offset(content, offset)),
+            column: Some(content[line_start..offset].chars().count() + 1),
+        }
+    }
+
+    /// posix path of the file, this is what SARIF expects
+    fn uri(&self) -> String {
+        self.file.to_string_lossy().replace('\\', "/")
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn from_warning(w: &Warning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: w.kind.code(),
+            package: Some(w.package.clone()).filter(|p| !p.is_empty()),
+            message: w.to_string(),
+            location: w.location.clone(),
+        }
+    }
+
+    /// the code and location come from the `NabsError` in the chain, if there is one
+    pub fn from_error(e: &anyhow::Error) -> Self {
+        let nabs_error = NabsError::find(e);
+        let package = match nabs_error {
+            Some(NabsError::AmbiguousInference { package, .. })
+            | Some(NabsError::NothingInferred { package }) => Some(package.clone()),
+            _ => None,
+        };
+        let location = match nabs_error {
+            Some(NabsError::ManifestParse { file, line, .. }) => Some(Location {
+                file: file.clone(),
+                line: *line,
+                column: None,
+            }),
+            Some(NabsError::UnreadableFile { path, .. }) => Some(Location::file(path)),
+            _ => None,
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            code: nabs_error.map_or(GENERIC_ERROR_CODE, |n| n.code()),
+            package,
+            message: format!("{:#}", e),
+            location,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.severity.as_str(),
+            self.code,
+            self.message
+        )?;
+        if let Some(location) = &self.location {
+            write!(f, "\n  --> {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Diagnostics {
+    format: DiagnosticsFormat,
+    warnings: Mutex<Vec<Warning>>,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Diagnostics::new(DiagnosticsFormat::Text)
+    }
+}
+
+impl Diagnostics {
+    pub fn new(format: DiagnosticsFormat) -> Self {
+        Diagnostics {
+            format,
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn format(&self) -> DiagnosticsFormat {
+        self.format
+    }
+
+    pub fn warn(&self, w: Warning) {
+        if self.format == DiagnosticsFormat::Text {
+            eprintln!("{}", Diagnostic::from_warning(&w));
+        }
+        self.warnings.lock().unwrap().push(w);
+    }
+
+    /// warnings found so far, the graph is missing some edges if this is not empty
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warnings.lock().unwrap().clone()
+    }
+
+    /// same as `warnings`, but they are removed, a long running process uses this to only keep the warnings of its last graph
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+
+    /// every warning, followed by `error` if the run failed
+    pub fn diagnostics(&self, error: Option<&anyhow::Error>) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = self
+            .warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Diagnostic::from_warning)
+            .collect();
+        diagnostics.extend(error.map(Diagnostic::from_error));
+        diagnostics
+    }
+
+    /// the report for the end of a run, `None` in text format since warnings are already printed by then
+    pub fn render(&self, error: Option<&anyhow::Error>) -> Result<Option<String>> {
+        let diagnostics = self.diagnostics(error);
+        match self.format {
+            DiagnosticsFormat::Text => Ok(None),
+            DiagnosticsFormat::Json => Ok(Some(render_json(&diagnostics)?)),
+            DiagnosticsFormat::Sarif => Ok(Some(render_sarif(&diagnostics)?)),
+        }
+    }
+}
+
+pub fn render_json(diagnostics: &[Diagnostic]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(
+        &json!({ "diagnostics": diagnostics }),
+    )?)
+}
+
+/// a SARIF 2.1.0 log with a single run, every diagnostic code is a rule
+/// file locations are relative to `%SRCROOT%`, the workspace root
+pub fn render_sarif(diagnostics: &[Diagnostic]) -> Result<String> {
+    let mut rules: Vec<&str> = Vec::new();
+    let mut results = Vec::new();
+    for d in diagnostics {
+        let rule_index = match rules.iter().position(|r| *r == d.code) {
+            Some(i) => i,
+            None => {
+                rules.push(d.code);
+                rules.len() - 1
+            }
+        };
+        let mut result = json!({
+            "ruleId": d.code,
+            "ruleIndex": rule_index,
+            "level": d.severity.as_str(),
+            "message": { "text": d.message },
+        });
+        if let Some(location) = &d.location {
+            let mut physical = json!({
+                "artifactLocation": { "uri": location.uri(), "uriBaseId": "%SRCROOT%" },
+            });
+            if let Some(line) = location.line {
+                let mut region = json!({ "startLine": line });
+                if let Some(column) = location.column {
+                    region["startColumn"] = json!(column);
+                }
+                physical["region"] = region;
+            }
+            result["locations"] = json!([{ "physicalLocation": physical }]);
+        }
+        if let Some(package) = &d.package {
+            result["properties"] = json!({ "package": package });
+        }
+        results.push(result);
+    }
+    let rules: Vec<_> = rules.iter().map(|r| json!({ "id": r })).collect();
+    Ok(serde_json::to_string_pretty(&json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "nabs",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    }))?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use anyhow::{Context, anyhow};
+
+    use crate::error::NabsError;
+    use crate::warning::{Warning, WarningKind};
+
+    use super::{Diagnostics, DiagnosticsFormat, Location};
+
+    #[test]
+    fn test_render_sarif() {
+        let content = "[dependencies]\nb = { path = \"../../outside\" }\n";
+        let location = Location::find(Path::new("libs/a/Cargo.toml"), content, "../../outside");
+        assert_eq!(location.line, Some(2));
+        assert_eq!(location.column, Some(15));
+
+        let diagnostics = Diagnostics::new(DiagnosticsFormat::Sarif);
+        diagnostics.warn(Warning {
+            kind: WarningKind::FailedParent,
+            package: "libs/a".to_string(),
+            subject: "../../outside".to_string(),
+            reason: "path points outside the workspace".to_string(),
+            location: Some(location),
+        });
+        let error = Err::<(), _>(NabsError::ManifestParse {
+            file: PathBuf::from("libs/b/composer.json"),
+            line: Some(3),
+            message: "expected value".to_string(),
+        })
+        .context(anyhow!("failed in creating graph"))
+        .unwrap_err();
+
+        let rendered = diagnostics.render(Some(&error)).unwrap().unwrap();
+        let sarif: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "failed-parent");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "manifest-parse");
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["level"], "warning");
+        let physical = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(physical["artifactLocation"]["uri"], "libs/a/Cargo.toml");
+        assert_eq!(physical["region"]["startLine"], 2);
+        assert_eq!(physical["region"]["startColumn"], 15);
+        assert_eq!(results[1]["level"], "error");
+        assert_eq!(results[1]["ruleIndex"], 1);
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            3
+        );
+    }
+}
@@ -0,0 +1,193 @@
+/// errors nabs can report to its caller
+/// functions still return `anyhow::Result`, these are created at the place the failure happens and can be found in the chain using `NabsError::find`
+/// every error has a stable `code` and a distinct process exit code, so that scripts can react to specific failures
+use std::{fmt::Display, path::PathBuf};
+
+/// exit code for failures which are not a `NabsError`
+pub const GENERIC_EXIT_CODE: i32 = 1;
+
+#[derive(Debug, Clone)]
+pub enum NabsError {
+    /// no `workspace.json` in the directory nabs was started from, or any of its parents
+    WorkspaceNotFound { searched_from: PathBuf },
+    /// a package name which is not a relative posix path without `.`/`..`
+    InvalidTargetName { name: String, reason: String },
+    /// more than one build system was detected for a package
+    AmbiguousInference {
+        package: String,
+        flavors: Vec<String>,
+    },
+    /// no build system was detected for a package
+    NothingInferred { package: String },
+    /// a manifest or config file which could not be parsed, `line` is 1 based
+    ManifestParse {
+        file: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// a relative path which points outside the workspace
+    PathEscapesWorkspace { path: PathBuf },
+    /// a file which exists but could not be read
+    UnreadableFile { path: PathBuf, message: String },
+    /// warnings were found in strict mode
+    DeniedWarnings { count: usize },
+    /// packages which depend on each other, `packages` starts and ends with the same package
+    DependencyCycle { packages: Vec<String> },
+}
+
+impl NabsError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            NabsError::WorkspaceNotFound { .. } => "workspace-not-found",
+            NabsError::InvalidTargetName { .. } => "invalid-target-name",
+            NabsError::AmbiguousInference { .. } => "ambiguous-inference",
+            NabsError::NothingInferred { .. } => "nothing-inferred",
+            NabsError::ManifestParse { .. } => "manifest-parse",
+            NabsError::PathEscapesWorkspace { .. } => "path-escapes-workspace",
+            NabsError::UnreadableFile { .. } => "unreadable-file",
+            NabsError::DeniedWarnings { .. } => "denied-warnings",
+            NabsError::DependencyCycle { .. } => "dependency-cycle",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NabsError::WorkspaceNotFound { .. } => 10,
+            NabsError::InvalidTargetName { .. } => 11,
+            NabsError::AmbiguousInference { .. } => 12,
+            NabsError::NothingInferred { .. } => 13,
+            NabsError::ManifestParse { .. } => 14,
+            NabsError::PathEscapesWorkspace { .. } => 15,
+            NabsError::UnreadableFile { .. } => 16,
+            NabsError::DeniedWarnings { .. } => 17,
+            NabsError::DependencyCycle { .. } => 18,
+        }
+    }
+
+    pub fn from_json(file: PathBuf, e: &serde_json::Error) -> Self {
+        NabsError::ManifestParse {
+            file,
+            line: Some(e.line()).filter(|l| *l > 0),
+            // the message from serde_json ends with the position, we show it ourselves
+            message: e
+                .to_string()
+                .trim_end_matches(&format!(" at line {} column {}", e.line(), e.column()))
+                .to_string(),
+        }
+    }
+
+    pub fn from_toml(file: PathBuf, content: &str, e: &toml::de::Error) -> Self {
+        NabsError::ManifestParse {
+            file,
+            line: e.span().map(|s| line_of_offset(content, s.start)),
+            message: e.message().to_string(),
+        }
+    }
+
+    pub fn from_yaml(file: PathBuf, e: &serde_yaml::Error) -> Self {
+        NabsError::ManifestParse {
+            file,
+            line: e.location().map(|l| l.line()),
+            message: e.to_string(),
+        }
+    }
+
+    /// the innermost `NabsError` in the chain of an error, closest to the root cause
+    /// this is the most specific reason for a failure, a `NabsError` added as context only describes where it happened
+    pub fn find(e: &anyhow::Error) -> Option<&NabsError> {
+        e.chain()
+            .filter_map(|c| c.downcast_ref::<NabsError>())
+            .last()
+    }
+}
+
+impl Display for NabsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NabsError::WorkspaceNotFound { searched_from } => write!(
+                f,
+                "could not find workspace.json in {:?} or any of its parents",
+                searched_from
+            ),
+            NabsError::InvalidTargetName { name, reason } => {
+                write!(f, "invalid target name={}, {}", name, reason)
+            }
+            NabsError::AmbiguousInference { package, flavors } => write!(
+                f,
+                "multiple build systems were inferred for package={}, this is not allowed for automatic inference. flavors={:?}",
+                package, flavors
+            ),
+            NabsError::NothingInferred { package } => {
+                write!(f, "could not infer any target for package={}", package)
+            }
+            NabsError::ManifestParse {
+                file,
+                line: Some(line),
+                message,
+            } => write!(
+                f,
+                "failed in parsing {:?} at line {}: {}",
+                file, line, message
+            ),
+            NabsError::ManifestParse {
+                file,
+                line: None,
+                message,
+            } => write!(f, "failed in parsing {:?}: {}", file, message),
+            NabsError::PathEscapesWorkspace { path } => {
+                write!(f, "path={:?} points outside the workspace", path)
+            }
+            NabsError::UnreadableFile { path, message } => {
+                write!(f, "failed in reading file={:?}: {}", path, message)
+            }
+            NabsError::DeniedWarnings { count } => write!(
+                f,
+                "found {} warnings in strict mode, fix them or allow them in workspace.json",
+                count
+            ),
+            NabsError::DependencyCycle { packages } => write!(
+                f,
+                "packages depend on each other, none of them can run first. cycle={}",
+                packages.join(" -> ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NabsError {}
+
+/// 1 based line of a byte offset in `content`
+pub fn line_of_offset(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use anyhow::{Context, anyhow};
+
+    use super::{NabsError, line_of_offset};
+
+    #[test]
+    fn test_find() {
+        let e = Err::<(), _>(NabsError::PathEscapesWorkspace {
+            path: PathBuf::from("../x"),
+        })
+        .context(anyhow!("failed in resolving parent"))
+        .context(anyhow!("failed in creating graph"))
+        .unwrap_err();
+        let found = NabsError::find(&e).unwrap();
+        assert_eq!(found.code(), "path-escapes-workspace");
+        assert_eq!(found.exit_code(), 15);
+
+        // the innermost one decides the exit code
+        let e = e.context(NabsError::NothingInferred {
+            package: "x".to_string(),
+        });
+        assert_eq!(NabsError::find(&e).unwrap().exit_code(), 15);
+
+        assert!(NabsError::find(&anyhow!("something else")).is_none());
+        assert_eq!(line_of_offset("a\nb\nc", 3), 2);
+    }
+}
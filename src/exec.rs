@@ -0,0 +1,421 @@
+/// runs a command in the directory of many packages, a package only starts once the packages it depends on succeeded
+/// packages which don't depend on each other run at the same time, up to a limit
+/// every line the command prints is prefixed with the package name
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::error::NabsError;
+use crate::graph::TargetGraph;
+use crate::types::{Repository, Target};
+
+/// a package to run the command in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub package: String,
+    /// absolute path of the package directory
+    pub dir: PathBuf,
+    /// positions of the tasks of the packages this one depends on
+    pub deps: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Succeeded,
+    Failed(String),
+    /// never started, along with the reason
+    Skipped(String),
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Succeeded => write!(f, "ok"),
+            Status::Failed(reason) => write!(f, "failed, {}", reason),
+            Status::Skipped(reason) => write!(f, "skipped, {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub package: String,
+    pub status: Status,
+    /// zero if the command never started
+    pub duration: Duration,
+}
+
+pub struct ExecOptions {
+    /// how many commands run at the same time
+    pub jobs: usize,
+    /// keep starting packages which don't depend on a failed one, instead of stopping at the first failure
+    pub keep_going: bool,
+}
+
+/// a task for every package of `targets`, in the order packages first appear
+/// a package depends on another if any of its targets depends on a target of the other one, directly
+/// `targets` from `TargetGraph::rdeps` are enough, a dependency between two of them never goes through a target which is not in there
+/// fails with `NabsError::DependencyCycle` if packages depend on each other, none of them could ever run
+pub fn tasks(repo: &dyn Repository, graph: &TargetGraph, targets: &[Target]) -> Result<Vec<Task>> {
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    let mut tasks: Vec<Task> = Vec::new();
+    for t in targets {
+        let package = t.name.to_string_ref().as_str();
+        if !positions.contains_key(package) {
+            positions.insert(package, tasks.len());
+            tasks.push(Task {
+                package: package.to_string(),
+                dir: repo
+                    .workspace_root()
+                    .join(repo.target_name_to_path(&t.name)),
+                deps: Vec::new(),
+            });
+        }
+    }
+    for t in targets {
+        let ours = positions[t.name.to_string_ref().as_str()];
+        let Some(id) = graph.id(t) else {
+            continue;
+        };
+        for p in graph.parent_ids(id) {
+            let parent = graph.target(p).name.to_string_ref().as_str();
+            if let Some(&theirs) = positions.get(parent)
+                && theirs != ours
+                && !tasks[ours].deps.contains(&theirs)
+            {
+                tasks[ours].deps.push(theirs);
+            }
+        }
+    }
+    if let Some(packages) = find_cycle(&tasks) {
+        return Err(NabsError::DependencyCycle { packages }.into());
+    }
+    Ok(tasks)
+}
+
+/// the packages of a cycle in `tasks`, each one depends on the next
+fn find_cycle(tasks: &[Task]) -> Option<Vec<String>> {
+    // tasks on the path from the task the search started at, and the ones known not to be in a cycle
+    let mut path: Vec<usize> = Vec::new();
+    let mut done = vec![false; tasks.len()];
+    for start in 0..tasks.len() {
+        if let Some(cycle) = visit(tasks, start, &mut path, &mut done) {
+            return Some(cycle.iter().map(|i| tasks[*i].package.clone()).collect());
+        }
+    }
+    None
+}
+
+fn visit(tasks: &[Task], i: usize, path: &mut Vec<usize>, done: &mut [bool]) -> Option<Vec<usize>> {
+    if done[i] {
+        return None;
+    }
+    if let Some(from) = path.iter().position(|p| *p == i) {
+        let mut cycle = path[from..].to_vec();
+        cycle.push(i);
+        return Some(cycle);
+    }
+    path.push(i);
+    for d in &tasks[i].deps {
+        if let Some(cycle) = visit(tasks, *d, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done[i] = true;
+    None
+}
+
+/// runs `command` (the program followed by its arguments) for every task, returns an outcome for every task in the same order
+/// `NABS_PACKAGE` is set to the name of the package
+pub fn run(tasks: &[Task], command: &[String], options: &ExecOptions) -> Vec<Outcome> {
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; tasks.len()];
+    let mut running: HashSet<usize> = HashSet::new();
+    let mut failed = false;
+    let (tx, rx) = mpsc::channel::<(usize, Status, Duration)>();
+
+    loop {
+        // skip packages which can never run
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, task) in tasks.iter().enumerate() {
+                if outcomes[i].is_some() || running.contains(&i) {
+                    continue;
+                }
+                let reason = if failed && !options.keep_going {
+                    Some("stopped after a failure".to_string())
+                } else {
+                    task.deps
+                        .iter()
+                        .find(|d| {
+                            outcomes[**d]
+                                .as_ref()
+                                .is_some_and(|o| o.status != Status::Succeeded)
+                        })
+                        .map(|d| format!("dependency {} did not succeed", tasks[*d].package))
+                };
+                if let Some(reason) = reason {
+                    outcomes[i] = Some(skipped(task, reason));
+                    changed = true;
+                }
+            }
+        }
+
+        let ready: Vec<usize> = (0..tasks.len())
+            .filter(|i| outcomes[*i].is_none() && !running.contains(i))
+            .filter(|i| {
+                tasks[*i].deps.iter().all(|d| {
+                    outcomes[*d]
+                        .as_ref()
+                        .is_some_and(|o| o.status == Status::Succeeded)
+                })
+            })
+            .collect();
+        for i in ready {
+            if running.len() >= options.jobs.max(1) {
+                break;
+            }
+            running.insert(i);
+            let tx = tx.clone();
+            let task = tasks[i].clone();
+            let command = command.to_vec();
+            std::thread::spawn(move || {
+                let start = Instant::now();
+                let status = run_one(&task, &command);
+                let _ = tx.send((i, status, start.elapsed()));
+            });
+        }
+
+        if running.is_empty() {
+            // whatever is left waits on itself, `tasks` never returns such tasks
+            for (i, task) in tasks.iter().enumerate() {
+                if outcomes[i].is_none() {
+                    outcomes[i] = Some(skipped(task, "dependency cycle".to_string()));
+                }
+            }
+            break;
+        }
+        let Ok((i, status, duration)) = rx.recv() else {
+            break;
+        };
+        running.remove(&i);
+        failed |= matches!(status, Status::Failed(_));
+        outcomes[i] = Some(Outcome {
+            package: tasks[i].package.clone(),
+            status,
+            duration,
+        });
+    }
+    outcomes.into_iter().flatten().collect()
+}
+
+fn skipped(task: &Task, reason: String) -> Outcome {
+    Outcome {
+        package: task.package.clone(),
+        status: Status::Skipped(reason),
+        duration: Duration::ZERO,
+    }
+}
+
+fn run_one(task: &Task, command: &[String]) -> Status {
+    let Some((program, args)) = command.split_first() else {
+        return Status::Failed("empty command".to_string());
+    };
+    let child = Command::new(program)
+        .args(args)
+        .current_dir(&task.dir)
+        .env("NABS_PACKAGE", &task.package)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(c) => c,
+        Err(e) => return Status::Failed(format!("failed in starting {}, {}", program, e)),
+    };
+    let stdout = child.stdout.take().map(|s| prefix(&task.package, s, false));
+    let stderr = child.stderr.take().map(|s| prefix(&task.package, s, true));
+    for printer in stdout.into_iter().chain(stderr) {
+        let _ = printer.join();
+    }
+    match child.wait() {
+        Ok(s) if s.success() => Status::Succeeded,
+        Ok(s) => Status::Failed(match s.code() {
+            Some(code) => format!("exit code {}", code),
+            None => "killed by a signal".to_string(),
+        }),
+        Err(e) => Status::Failed(e.to_string()),
+    }
+}
+
+/// prints every line of `output` prefixed with the package, lines of different packages don't mix
+fn prefix(
+    package: &str,
+    output: impl Read + Send + 'static,
+    stderr: bool,
+) -> std::thread::JoinHandle<()> {
+    let package = package.to_string();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(output);
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            match stderr {
+                true => eprintln!("[{}] {}", package, text),
+                false => println!("[{}] {}", package, text),
+            }
+            line.clear();
+        }
+    })
+}
+
+/// a table with a row for every outcome
+pub fn summary(outcomes: &[Outcome]) -> String {
+    let width = outcomes
+        .iter()
+        .map(|o| o.package.len())
+        .chain(["package".len()])
+        .max()
+        .unwrap_or_default();
+    let mut table = format!("{:<width$}  {:>8}  status\n", "package", "time");
+    for o in outcomes {
+        let time = match o.status {
+            Status::Skipped(_) => "-".to_string(),
+            _ => format!("{:.1}s", o.duration.as_secs_f64()),
+        };
+        table.push_str(&format!(
+            "{:<width$}  {:>8}  {}\n",
+            o.package, time, o.status
+        ));
+    }
+    table
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    use super::{ExecOptions, Status, Task, run, summary, tasks};
+    use crate::error::NabsError;
+    use crate::graph::{EdgeKind, TargetGraph};
+    use crate::types::{MockRepo, Target};
+
+    #[test]
+    fn test_tasks() {
+        let target =
+            |n: &str, f: &str| Target::from_string_name(n.to_string(), f.to_string()).unwrap();
+        let (core, core_py, web) = (
+            target("libs/core", "cargo"),
+            target("libs/core", "python_requirements"),
+            target("apps/web", "cargo"),
+        );
+        let mut g = TargetGraph::new();
+        for t in [&core, &core_py, &web] {
+            g.add_node(t.clone());
+        }
+        g.add_edge(&core, &web, EdgeKind::Declared).unwrap();
+        g.add_edge(&core_py, &web, EdgeKind::Declared).unwrap();
+        let repo = MockRepo::new(HashMap::new(), PathBuf::from("/ws"));
+        assert_eq!(
+            tasks(&repo, &g, &[core.clone(), core_py, web.clone()]).unwrap(),
+            vec![
+                Task {
+                    package: "libs/core".to_string(),
+                    dir: PathBuf::from("/ws/libs/core"),
+                    deps: vec![],
+                },
+                Task {
+                    package: "apps/web".to_string(),
+                    dir: PathBuf::from("/ws/apps/web"),
+                    deps: vec![0],
+                },
+            ]
+        );
+
+        // web -> core -> web, nothing can run
+        g.add_edge(&web, &core, EdgeKind::Declared).unwrap();
+        let err = tasks(&repo, &g, &[core, web]).unwrap_err();
+        match NabsError::find(&err) {
+            Some(NabsError::DependencyCycle { packages }) => {
+                assert_eq!(packages, &["libs/core", "apps/web", "libs/core"])
+            }
+            other => panic!("expected a dependency cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("nabs-exec-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let task = |package: &str, deps: Vec<usize>| Task {
+            package: package.to_string(),
+            dir: dir.clone(),
+            deps,
+        };
+        // b and c need a, d needs c, e fails and f needs e
+        let tasks = vec![
+            task("a", vec![]),
+            task("b", vec![0]),
+            task("c", vec![0]),
+            task("d", vec![2]),
+            task("e", vec![]),
+            task("f", vec![4]),
+        ];
+        let command: Vec<String> = [
+            "sh",
+            "-c",
+            r#"echo $NABS_PACKAGE >> order; test $NABS_PACKAGE != e"#,
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let options = ExecOptions {
+            jobs: 2,
+            keep_going: true,
+        };
+        let outcomes = run(&tasks, &command, &options);
+        let status: Vec<&Status> = outcomes.iter().map(|o| &o.status).collect();
+        assert_eq!(status[..4], [&Status::Succeeded; 4]);
+        assert_eq!(status[4], &Status::Failed("exit code 1".to_string()));
+        assert_eq!(
+            status[5],
+            &Status::Skipped("dependency e did not succeed".to_string())
+        );
+        let order = fs::read_to_string(dir.join("order")).unwrap();
+        let position = |p: &str| order.lines().position(|l| l == p).unwrap();
+        assert!(position("a") < position("b"));
+        assert!(position("c") < position("d"));
+        assert!(summary(&outcomes).contains("skipped, dependency e did not succeed"));
+
+        // without keep going, nothing starts after e fails
+        fs::remove_file(dir.join("order")).unwrap();
+        let options = ExecOptions {
+            jobs: 1,
+            keep_going: false,
+        };
+        let outcomes = run(
+            &tasks[4..5]
+                .iter()
+                .chain(&tasks[..1])
+                .cloned()
+                .collect::<Vec<_>>(),
+            &command,
+            &options,
+        );
+        assert_eq!(
+            outcomes[1].status,
+            Status::Skipped("stopped after a failure".to_string())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
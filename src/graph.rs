@@ -1,138 +1,265 @@
-use std::{collections::HashMap, fmt::Display, ops::Deref, rc::Rc};
+use std::{
+    collections::{HashMap, VecDeque, hash_map::Entry},
+    fmt::Display,
+    hash::{BuildHasher, RandomState},
+};
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Result, anyhow};
+use hashbrown::HashTable;
 use petgraph::{
-    Graph,
+    Direction,
     graph::NodeIndex,
-    visit::{Dfs, Visitable},
+    stable_graph::StableGraph,
+    visit::{Dfs, EdgeRef, IntoEdgeReferences, Reversed, Visitable},
 };
 
 use crate::types::Target;
 
-// petgraph has a whole notion of only using copy-able indices for their graph
-// everything happens in the form of `NodeIndex`, its hard to get what "index" some node is natively from petgraph
-// as such, we manage indices ourselves in our fat structure
-// for nodes themselves, i dont care what data goes into them (it could simply be ()) while we keep track of true nodes
-// nodes in our hashmap
-// I would love to use GraphMap directly, but i cant implement `Copy` on my package
-// I could keep track of some random unique integer for that, but thats the same work as simply storing indices
-// we make a ridiculous number of clone calls for `NodeIndex`, this is fine as its just a u32
+/// the reason an edge exists between two targets
+/// all kinds are treated the same way in queries, the kind is kept to explain where an edge came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// declared in a manifest file of the package, like `Cargo.toml` or `requirements.txt`
+    Declared,
+    /// found by scanning the source code of the package, the dependency was never declared anywhere
+    InferredImport,
+}
+
+impl Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeKind::Declared => write!(f, "declared"),
+            EdgeKind::InferredImport => write!(f, "inferred-import"),
+        }
+    }
+}
+
+/// a target interned in a `TargetGraph`, ids are handed out in the order targets are added and are never reused
+/// queries on ids don't hash or clone any target, use these when answering many queries on the same graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TargetId(u32);
+
+impl TargetId {
+    /// the position of the target in `TargetGraph::ids`
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    fn node(self) -> NodeIndex {
+        NodeIndex::new(self.0 as usize)
+    }
+
+    fn from_node(node: NodeIndex) -> Self {
+        TargetId(node.index() as u32)
+    }
+}
+
+// an edge goes from a dependency to the target depending on it, so `rdeps` walks edges forward
+// nodes are never removed, so the `NodeIndex` of a target is always the same number as its `TargetId`
+// `StableGraph` keeps it that way even if edges are removed later
 pub struct TargetGraph {
-    // the main inner graph
-    // make this acyclic
-    inner: Graph<(), ()>,
-
-    // used for lookup
-    // for now im keeping copies here, its hard to wrap
-    // my head around what would work
-    target_by_index: HashMap<Rc<Target>, NodeIndex>,
-
-    // honestly at this point, i dont see a point of using this stupid lib
-    // ive to keep both mappings to make it any reasonably fast
-    index_by_target: HashMap<NodeIndex, Rc<Target>>,
+    inner: StableGraph<(), EdgeKind>,
+    // indexed by `TargetId`
+    targets: Vec<Target>,
+    // hashes and compares through `targets[id]`, so each target is stored only once
+    ids: HashTable<TargetId>,
+    hasher: RandomState,
+}
+
+impl Default for TargetGraph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TargetGraph {
     pub fn new() -> Self {
         TargetGraph {
-            inner: Graph::new(),
-            target_by_index: HashMap::new(),
-            index_by_target: HashMap::new(),
+            inner: StableGraph::new(),
+            targets: Vec::new(),
+            ids: HashTable::new(),
+            hasher: RandomState::new(),
         }
     }
 
     pub fn contains_node(&self, node: &Target) -> bool {
-        let index = self.target_by_index.get(node);
-        match index {
-            None => false,
-            Some(v) => match self.inner.node_weight(v.clone()) {
-                None => false,
-                Some(_) => true,
-            },
-        }
+        self.id(node).is_some()
     }
 
-    pub fn add_node(&mut self, node: Target) {
-        if self.target_by_index.contains_key(&node) {
-            return;
+    /// adds a target if it is not in the graph yet, returns its id either way
+    pub fn add_node(&mut self, node: Target) -> TargetId {
+        let hash = self.hasher.hash_one(&node);
+        if let Some(id) = self.find(hash, &node) {
+            return id;
         }
-        let index = self.inner.add_node(());
-        let node = Rc::new(node);
-        self.index_by_target.insert(index, Rc::clone(&node));
-        self.target_by_index.insert(node, index);
+        let id = TargetId::from_node(self.inner.add_node(()));
+        self.targets.push(node);
+        let (targets, hasher) = (&self.targets, &self.hasher);
+        self.ids
+            .insert_unique(hash, id, |id| hasher.hash_one(&targets[id.index()]));
+        id
     }
 
-    pub fn add_edge(&mut self, src: &Target, dest: &Target) -> Result<()> {
-        let s = self.get_cloned_node_index(src)?;
-        let d = self.get_cloned_node_index(dest)?;
-        if self.inner.contains_edge(s.clone(), d.clone()) {
+    /// adds an edge, if an edge already exists between `src` and `dest`, the old kind is kept
+    pub fn add_edge(&mut self, src: &Target, dest: &Target, kind: EdgeKind) -> Result<()> {
+        let s = self.get_id(src)?.node();
+        let d = self.get_id(dest)?.node();
+        if self.inner.contains_edge(s, d) {
             return Ok(());
         }
-        self.inner.add_edge(s, d, ());
+        self.inner.add_edge(s, d, kind);
         Ok(())
     }
 
-    fn get_cloned_node_index(&self, target: &Target) -> Result<NodeIndex> {
-        let d = self
-            .target_by_index
-            .get(target)
-            .ok_or(anyhow!(
-                "error while adding edge: dest index: {:?} not found",
-                target
-            ))?
-            .clone();
-        Ok(d)
+    /// the id of a target in the graph
+    pub fn id(&self, target: &Target) -> Option<TargetId> {
+        self.find(self.hasher.hash_one(target), target)
     }
 
-    pub fn rdeps(&self, targets: &Vec<Target>) -> Result<Vec<Target>> {
-        let mut indices = Vec::new();
-        for t in targets {
-            indices.push(self.get_cloned_node_index(t)?);
-        }
-        let mut dfs = Dfs::from_parts(indices, self.inner.visit_map());
+    fn find(&self, hash: u64, target: &Target) -> Option<TargetId> {
+        self.ids
+            .find(hash, |id| &self.targets[id.index()] == target)
+            .copied()
+    }
+
+    /// the target an id was handed out for, panics if the id is from another graph
+    pub fn target(&self, id: TargetId) -> &Target {
+        &self.targets[id.0 as usize]
+    }
+
+    /// number of targets in the graph
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// ids of every target, in the order they were added
+    pub fn ids(&self) -> impl Iterator<Item = TargetId> + use<> {
+        (0..self.targets.len() as u32).map(TargetId)
+    }
+
+    /// every edge, from the dependency to the target depending on it
+    pub fn edges(&self) -> impl Iterator<Item = (TargetId, TargetId, EdgeKind)> + '_ {
+        self.inner.edge_references().map(|e| {
+            (
+                TargetId::from_node(e.source()),
+                TargetId::from_node(e.target()),
+                *e.weight(),
+            )
+        })
+    }
+
+    fn get_id(&self, target: &Target) -> Result<TargetId> {
+        self.id(target)
+            .ok_or_else(|| anyhow!("target={:?} is not in the graph", target))
+    }
+
+    fn get_ids(&self, targets: &[Target]) -> Result<Vec<TargetId>> {
+        targets.iter().map(|t| self.get_id(t)).collect()
+    }
+
+    fn get_targets(&self, ids: Vec<TargetId>) -> Vec<Target> {
+        ids.into_iter().map(|id| self.target(id).clone()).collect()
+    }
+
+    /// every target affected by a change in `targets` (the ones depending on them, transitively), including `targets`
+    pub fn rdeps(&self, targets: &[Target]) -> Result<Vec<Target>> {
+        Ok(self.get_targets(self.rdeps_ids(&self.get_ids(targets)?)))
+    }
+
+    /// every target `targets` depend on, transitively, including `targets`
+    pub fn deps(&self, targets: &[Target]) -> Result<Vec<Target>> {
+        Ok(self.get_targets(self.deps_ids(&self.get_ids(targets)?)))
+    }
+
+    /// same as `rdeps`, for targets already interned in this graph
+    pub fn rdeps_ids(&self, ids: &[TargetId]) -> Vec<TargetId> {
+        let mut dfs = Dfs::from_parts(
+            ids.iter().map(|id| id.node()).collect(),
+            self.inner.visit_map(),
+        );
         let mut res = Vec::new();
-        while let Some(next_index) = dfs.next(&self.inner) {
-            let node =self.index_by_target.get(&next_index).expect(
-                &format!("corrupted graph state, `index_by_target` did not contain an index we got from dfs in graph, index={:?}", next_index)
-            );
+        while let Some(next) = dfs.next(&self.inner) {
+            res.push(TargetId::from_node(next));
+        }
+        res
+    }
 
-            res.push(node.deref().clone());
+    /// same as `deps`, for targets already interned in this graph
+    pub fn deps_ids(&self, ids: &[TargetId]) -> Vec<TargetId> {
+        let reversed = Reversed(&self.inner);
+        let mut dfs = Dfs::from_parts(
+            ids.iter().map(|id| id.node()).collect(),
+            self.inner.visit_map(),
+        );
+        let mut res = Vec::new();
+        while let Some(next) = dfs.next(reversed) {
+            res.push(TargetId::from_node(next));
         }
+        res
+    }
 
-        Ok(res)
+    /// the targets `id` depends on directly
+    pub fn parent_ids(&self, id: TargetId) -> Vec<TargetId> {
+        self.inner
+            .neighbors_directed(id.node(), Direction::Incoming)
+            .map(TargetId::from_node)
+            .collect()
+    }
+
+    /// the shortest chain of dependents leading from one of `from` to one of `to`, both included
+    /// this explains why a change in `from` affects `to`, `None` if it doesn't
+    pub fn path_ids(&self, from: &[TargetId], to: &[TargetId]) -> Option<Vec<TargetId>> {
+        let mut previous: HashMap<TargetId, Option<TargetId>> =
+            from.iter().map(|id| (*id, None)).collect();
+        let mut queue: VecDeque<TargetId> = from.iter().copied().collect();
+        while let Some(id) = queue.pop_front() {
+            if to.contains(&id) {
+                let mut path = vec![id];
+                while let Some(Some(p)) = previous.get(path.last().unwrap()) {
+                    path.push(*p);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for n in self.inner.neighbors(id.node()) {
+                let n = TargetId::from_node(n);
+                if let Entry::Vacant(e) = previous.entry(n) {
+                    e.insert(Some(id));
+                    queue.push_back(n);
+                }
+            }
+        }
+        None
     }
 
     /// given a target, return all neighbors, or the outgoing edges (calling it neighbors to mirror `petgraph`'s API)
-    /// does cloning, useful for tests, if using internally, directly use self.inner.neighbors for normal graph walking
+    /// does cloning, useful for tests
     pub fn neighbors(&self, target: &Target) -> Result<Vec<Target>> {
-        let node_idx = self.get_cloned_node_index(target)?;
-        let mut neighbors = Vec::new();
-        for neighbor_idx in self.inner.neighbors(node_idx) {
-            let neighbor = self.index_by_target.get(&neighbor_idx).context(
-                anyhow!("corrupted graph state, `index_by_target` did not contain an index we got from neighbors in graph, index={:?}", neighbor_idx)
-            )?;
-            neighbors.push(neighbor.deref().clone());
-        }
-        Ok(neighbors)
+        let id = self.get_id(target)?;
+        Ok(self
+            .inner
+            .neighbors(id.node())
+            .map(|n| self.target(TargetId::from_node(n)).clone())
+            .collect())
     }
 }
 
 impl Display for TargetGraph {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for node_idx in self.inner.node_indices() {
-            let node = self
-                .index_by_target
-                .get(&node_idx)
-                .expect("corrupted graph state");
-            write!(f, "{} -> ", node)?;
+            write!(f, "{} -> ", self.target(TargetId::from_node(node_idx)))?;
 
             let mut neighbors = Vec::new();
-            for neighbor_idx in self.inner.neighbors(node_idx) {
-                let neighbor = self
-                    .index_by_target
-                    .get(&neighbor_idx)
-                    .expect("corrupted graph state");
-                neighbors.push(format!("{}", neighbor));
+            for edge in self.inner.edges(node_idx) {
+                let neighbor = self.target(TargetId::from_node(edge.target()));
+                match edge.weight() {
+                    EdgeKind::Declared => neighbors.push(format!("{}", neighbor)),
+                    kind => neighbors.push(format!("{} ({})", neighbor, kind)),
+                }
             }
 
             writeln!(f, "[{}]", neighbors.join(", "))?;
@@ -143,7 +270,7 @@ impl Display for TargetGraph {
 
 #[cfg(test)]
 mod test {
-    use super::TargetGraph;
+    use super::{EdgeKind, TargetGraph};
     use crate::types::Target;
 
     #[test]
@@ -181,26 +308,31 @@ mod test {
         g.add_node(cathode.clone());
 
         // qsync_stream -> image_manager
-        g.add_edge(&qsync_stream, &image_manager).unwrap();
+        g.add_edge(&qsync_stream, &image_manager, EdgeKind::Declared)
+            .unwrap();
 
         // qxr -> qxr_reports -> cathode
         // qxr -> cathode
         // qxr -> qureapi
-        g.add_edge(&qxr, &qxr_reports).unwrap();
-        g.add_edge(&qxr_reports, &cathode).unwrap();
-        g.add_edge(&qxr, &cathode).unwrap();
-        g.add_edge(&qxr, &qureapi).unwrap();
+        g.add_edge(&qxr, &qxr_reports, EdgeKind::Declared).unwrap();
+        g.add_edge(&qxr_reports, &cathode, EdgeKind::Declared)
+            .unwrap();
+        g.add_edge(&qxr, &cathode, EdgeKind::Declared).unwrap();
+        g.add_edge(&qxr, &qureapi, EdgeKind::Declared).unwrap();
 
         // qer -> qer_reports -> qureapi
         // qer -> qureapi
-        g.add_edge(&qer, &qer_reports).unwrap();
-        g.add_edge(&qer_reports, &qureapi).unwrap();
-        g.add_edge(&qer, &qureapi).unwrap();
+        g.add_edge(&qer, &qer_reports, EdgeKind::Declared).unwrap();
+        g.add_edge(&qer_reports, &qureapi, EdgeKind::Declared)
+            .unwrap();
+        g.add_edge(&qer, &qureapi, EdgeKind::Declared).unwrap();
 
         // qure_dicom_utils -> qxr
         // qure_dicom_utils -> qer
-        g.add_edge(&qure_dicom_utils, &qxr).unwrap();
-        g.add_edge(&qure_dicom_utils, &qer).unwrap();
+        g.add_edge(&qure_dicom_utils, &qxr, EdgeKind::Declared)
+            .unwrap();
+        g.add_edge(&qure_dicom_utils, &qer, EdgeKind::Declared)
+            .unwrap();
 
         let res = g.rdeps(&vec![qxr.clone()]).unwrap();
         assert!(res.contains(&qureapi));
@@ -222,4 +354,55 @@ mod test {
         assert!(res.contains(&image_manager));
         assert_eq!(res.len(), 2);
     }
+
+    #[test]
+    fn test_deps() {
+        let mut g = TargetGraph::new();
+        let lib = Target::from_string_name(String::from("lib"), String::from("cargo")).unwrap();
+        let core = Target::from_string_name(String::from("core"), String::from("cargo")).unwrap();
+        let app = Target::from_string_name(String::from("app"), String::from("cargo")).unwrap();
+        let other = Target::from_string_name(String::from("other"), String::from("cargo")).unwrap();
+        g.add_node(lib.clone());
+        g.add_node(core.clone());
+        g.add_node(app.clone());
+        g.add_node(other.clone());
+
+        // lib -> core -> app
+        // lib -> other
+        g.add_edge(&lib, &core, EdgeKind::Declared).unwrap();
+        g.add_edge(&core, &app, EdgeKind::Declared).unwrap();
+        g.add_edge(&lib, &other, EdgeKind::Declared).unwrap();
+
+        let res = g.deps(&[app.clone()]).unwrap();
+        assert!(res.contains(&app));
+        assert!(res.contains(&core));
+        assert!(res.contains(&lib));
+        assert_eq!(res.len(), 3);
+
+        let res = g.deps(&[lib.clone()]).unwrap();
+        assert_eq!(res, vec![lib.clone()]);
+    }
+
+    #[test]
+    fn test_ids() {
+        let mut g = TargetGraph::new();
+        let lib = Target::from_string_name(String::from("lib"), String::from("cargo")).unwrap();
+        let app = Target::from_string_name(String::from("app"), String::from("cargo")).unwrap();
+        let lib_id = g.add_node(lib.clone());
+        let app_id = g.add_node(app.clone());
+        assert_eq!(g.add_node(lib.clone()), lib_id);
+        assert_eq!(g.len(), 2);
+        assert_eq!(g.id(&app), Some(app_id));
+        assert_eq!(g.target(lib_id), &lib);
+
+        g.add_edge(&lib, &app, EdgeKind::Declared).unwrap();
+        assert_eq!(g.rdeps_ids(&[lib_id]), vec![lib_id, app_id]);
+        assert_eq!(g.deps_ids(&[app_id]), vec![app_id, lib_id]);
+        assert_eq!(g.path_ids(&[lib_id], &[app_id]), Some(vec![lib_id, app_id]));
+        assert_eq!(g.path_ids(&[app_id], &[lib_id]), None);
+        assert_eq!(g.parent_ids(app_id), vec![lib_id]);
+        assert_eq!(g.parent_ids(lib_id), vec![]);
+        let missing = Target::from_string_name(String::from("x"), String::from("cargo")).unwrap();
+        assert!(g.rdeps(&[missing]).is_err());
+    }
 }
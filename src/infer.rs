@@ -1,36 +1,274 @@
+mod bazel;
+mod cmake;
+mod composer;
+mod configured;
 mod core;
+mod gemfile;
 mod icargo;
+mod nix;
+mod plugin;
+mod proto;
+mod pubspec;
+mod py_imports;
 mod py_requirements;
+mod wasm;
 
-use core::{FailedParent, Infer, InferredTarget, Next, Single};
-use std::rc::Rc;
+use bazel::BazelInfer;
+use cmake::CMakeInfer;
+use composer::ComposerInfer;
+use configured::ConfiguredInfer;
+pub use core::{
+    FailedParent, Infer, InferResult, InferredTarget, LinkInfer, Next, Single,
+    locate_failed_parents, resolve_parent,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
-use anyhow::{Context, Result, bail};
-use icargo::CargoInfer;
+use anyhow::{Context, Result};
+use gemfile::GemfileInfer;
+pub use icargo::{CargoInfer, CargoUsageProblem};
+use log::info;
+use nix::NixInfer;
+use plugin::PluginInfer;
+use proto::{ProtoCodegenLink, ProtoInfer};
+use pubspec::PubspecInfer;
+use py_imports::{PyImportsLink, PySourcesInfer};
 use py_requirements::{DEFAULT_REQ_FILE_NAME, PyRequirementsInfer};
+use rayon::prelude::*;
+use wasm::WasmInfer;
 
-use crate::graph::TargetGraph;
+use crate::cache::{CACHE_DIR, InferenceCache, recording};
+use crate::config::WorkspaceConfig;
+use crate::diagnostics::{Diagnostics, Location};
+use crate::error::NabsError;
+use crate::graph::{EdgeKind, TargetGraph};
 use crate::types::{RawTarget, Repository, Target};
+use crate::warning::{Warning, WarningKind};
+
+/// a `Single` along with the parents all `LinkInfer`s found for it
+struct LinkedSingle {
+    single: Single,
+    linked: Vec<(RawTarget, EdgeKind)>,
+}
+
+/// the result of `run_inf` for a package, kept in the memo table of `InferRunner`
+type Memoized = Result<Vec<LinkedSingle>, Failure>;
+
+/// a failed inference, `anyhow::Error` can't be cloned so its chain is kept instead
+/// every package depending on a failed package gets the same error back, without running inference again
+#[derive(Debug)]
+struct Failure {
+    // outermost message first
+    messages: Vec<String>,
+    // the root cause, if it is a `NabsError` (these never have a source)
+    root: Option<NabsError>,
+}
+
+impl Failure {
+    fn new(e: &anyhow::Error) -> Self {
+        let root = e.root_cause().downcast_ref::<NabsError>().cloned();
+        let mut messages: Vec<String> = e.chain().map(|c| c.to_string()).collect();
+        if root.is_some() {
+            messages.pop();
+        }
+        Failure { messages, root }
+    }
+
+    /// an error with the same chain as the one this was created from
+    fn to_error(&self) -> anyhow::Error {
+        let mut messages = self.messages.iter().rev();
+        let mut e = match &self.root {
+            Some(root) => anyhow::Error::new(root.clone()),
+            None => anyhow::Error::msg(messages.next().cloned().unwrap_or_default()),
+        };
+        for m in messages {
+            e = e.context(m.clone());
+        }
+        e
+    }
+}
+
+/// how many times `InferRunner` reused an inference result instead of reading a package's manifests again
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoStats {
+    /// packages inferred, each one exactly once
+    pub misses: usize,
+    /// lookups answered by the memo table, failures included
+    pub hits: usize,
+}
 
 pub struct InferRunner {
     infers: Vec<Box<dyn Infer>>,
+    // only run for packages none of `infers` detected
+    fallbacks: Vec<Box<dyn Infer>>,
+    links: Vec<Box<dyn LinkInfer>>,
+    // every warning found while building graphs is reported here
+    diagnostics: Arc<Diagnostics>,
+    cache: Option<Arc<InferenceCache>>,
+    // every package this runner inferred, a manifest is parsed once no matter how many packages depend on it
+    memo: Mutex<HashMap<RawTarget, Arc<Memoized>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
 }
 
 impl InferRunner {
     pub fn new(infers: Vec<Box<dyn Infer>>) -> Self {
-        InferRunner { infers }
+        InferRunner {
+            infers,
+            fallbacks: Vec::new(),
+            links: Vec::new(),
+            diagnostics: Arc::new(Diagnostics::default()),
+            cache: None,
+            memo: Mutex::new(HashMap::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
     }
 
-    pub fn default(repo: &Rc<dyn Repository>) -> Self {
-        InferRunner {
-            infers: vec![
-                Box::new(CargoInfer::new(Rc::clone(repo))),
-                Box::new(PyRequirementsInfer::new(
-                    Rc::clone(repo),
-                    DEFAULT_REQ_FILE_NAME.to_string(),
-                )),
-            ],
+    /// drops the memoized result of a package, it is inferred again the next time a graph needs it
+    /// returns `false` if the package was never inferred
+    pub fn forget(&self, raw: &RawTarget) -> bool {
+        self.memo.lock().unwrap().remove(raw).is_some()
+    }
+
+    /// drops every memoized result, the next graph is inferred from scratch (the on-disk cache is still used)
+    /// links forget what they know about the workspace too, call this when packages are added or removed
+    pub fn forget_all(&self) {
+        self.memo.lock().unwrap().clear();
+        for link in &self.links {
+            link.reset();
+        }
+    }
+
+    /// hits and misses of the memo table, across every graph built by this runner
+    /// the memo is kept for the lifetime of the runner, use `forget` or `forget_all` to infer packages again
+    pub fn memo_stats(&self) -> MemoStats {
+        MemoStats {
+            misses: self.misses.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+        }
+    }
+
+    /// warnings found so far, the graph is missing some edges if this is not empty
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.diagnostics.warnings()
+    }
+
+    fn warn(&self, w: Warning) {
+        self.diagnostics.warn(w);
+    }
+
+    /// report warnings to a sink shared with the caller, by default every runner has its own
+    pub fn with_diagnostics(mut self, diagnostics: Arc<Diagnostics>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// skip inference for packages whose inputs did not change since they were cached
+    /// the inferrers have to read through a `RecordingRepo`, otherwise nothing is cached
+    pub fn with_cache(mut self, cache: Arc<InferenceCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_links(mut self, links: Vec<Box<dyn LinkInfer>>) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// inferrers for packages which have no manifest of their own, they only run if no other inferrer detected anything
+    /// unlike `infers`, these can match files which are common in packages of any build system (like `.py` scripts)
+    pub fn with_fallbacks(mut self, fallbacks: Vec<Box<dyn Infer>>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// every file (as globs relative to a package) any inferrer or link reads parents from
+    /// `None` if one of them can't tell, see `Infer::manifests`
+    pub fn manifests(&self) -> Option<Vec<String>> {
+        let infers = self
+            .infers
+            .iter()
+            .chain(&self.fallbacks)
+            .map(|i| i.manifests());
+        let links = self.links.iter().map(|l| l.manifests());
+        let mut manifests: Vec<String> = infers.chain(links).collect::<Option<Vec<_>>>()?.concat();
+        manifests.sort();
+        manifests.dedup();
+        Some(manifests)
+    }
+
+    pub fn default(repo: &Arc<dyn Repository>, config: &WorkspaceConfig) -> Result<Self> {
+        let mut runner = InferRunner::new(vec![
+            Box::new(CargoInfer::new(Arc::clone(repo))),
+            Box::new(PyRequirementsInfer::new(
+                Arc::clone(repo),
+                DEFAULT_REQ_FILE_NAME.to_string(),
+            )),
+            Box::new(ComposerInfer::new(Arc::clone(repo))),
+            Box::new(PubspecInfer::new(Arc::clone(repo))),
+        ]);
+        if config.enable.cmake {
+            runner
+                .infers
+                .push(Box::new(CMakeInfer::new(Arc::clone(repo))));
+        }
+        if config.enable.bazel {
+            runner
+                .infers
+                .push(Box::new(BazelInfer::new(Arc::clone(repo))));
+        }
+        if config.enable.gemfile {
+            runner
+                .infers
+                .push(Box::new(GemfileInfer::new(Arc::clone(repo))));
+        }
+        if config.enable.nix {
+            runner
+                .infers
+                .push(Box::new(NixInfer::new(Arc::clone(repo))));
+        }
+        // proto packages are only detected with include roots, without them `proto_sources` could never be inferred
+        if !config.proto.include_roots.is_empty() {
+            runner.infers.push(Box::new(ProtoInfer::new(
+                Arc::clone(repo),
+                config.proto.include_roots.clone(),
+            )));
+            runner
+                .links
+                .push(Box::new(ProtoCodegenLink::new(Arc::clone(repo))));
+        }
+        if config.python.scan_imports {
+            runner
+                .links
+                .push(Box::new(PyImportsLink::new(Arc::clone(repo))));
+            // packages which never declared anything still need a target for their imports to be linked to
+            runner
+                .fallbacks
+                .push(Box::new(PySourcesInfer::new(Arc::clone(repo))));
+        }
+        for inferrer in &config.inferrers {
+            runner.infers.push(Box::new(ConfiguredInfer::new(
+                Arc::clone(repo),
+                inferrer.clone(),
+            )?));
         }
+        for plugin in &config.plugins {
+            runner
+                .infers
+                .push(Box::new(PluginInfer::new(Arc::clone(repo), plugin.clone())));
+        }
+        for plugin in &config.wasm_plugins {
+            runner
+                .infers
+                .push(Box::new(WasmInfer::new(Arc::clone(repo), plugin.clone())?));
+        }
+        Ok(runner)
     }
 
     // given a set of raw targets to start from
@@ -39,15 +277,66 @@ impl InferRunner {
     // what do we do in that case?
     // an example is returning {name: a, flavor: cargo}, {name: a, flavor: poetry}
     // in this case, flavor should match of the returned parent dep
+    //
+    // inference runs in parallel first, for every package reachable from `start`
+    // the graph is then assembled sequentially in the order of `start`, so the result and the warnings are the same as a sequential run
+    /// packages inferred by an earlier graph of this runner are taken from its memo, they are not read again
+    /// the memo lives as long as the runner (`LiveGraph` relies on this), see `forget` and `forget_all`
     pub fn build_graph<I>(&self, start: I) -> Result<(TargetGraph, Vec<Target>)>
     where
         I: IntoIterator<Item = RawTarget>,
     {
+        self.build_graph_excluding(start, &HashSet::new())
+    }
+
+    /// same as `build_graph`, but parents in `excluded` are neither inferred nor added to the graph
+    /// used to build only the part of the graph a change can reach, see `Workspace::build_rdeps_graph`
+    pub fn build_graph_excluding<I>(
+        &self,
+        start: I,
+        excluded: &HashSet<RawTarget>,
+    ) -> Result<(TargetGraph, Vec<Target>)>
+    where
+        I: IntoIterator<Item = RawTarget>,
+    {
+        let start: Vec<RawTarget> = start.into_iter().collect();
+        self.infer_all(&start, excluded);
+        if let Some(cache) = &self.cache {
+            // the graph is fine without the cache, it is only slower next time
+            if let Err(e) = cache.save() {
+                self.warn(Warning {
+                    kind: WarningKind::CacheNotSaved,
+                    package: String::new(),
+                    subject: CACHE_DIR.to_string(),
+                    reason: format!("{:#}", e),
+                    location: None,
+                });
+            }
+        }
         let mut g = TargetGraph::new();
         let mut our_targets = Vec::new();
         for s in start {
-            our_targets.extend(self.build_graph_rec(&mut g, &s)?);
+            match self.build_graph_rec(&mut g, &s, excluded) {
+                Ok(targets) => our_targets.extend(targets),
+                // a package we can't read is skipped, same as a parent failing in `build_graph_rec`
+                // any other failure means the workspace itself is broken
+                Err(e) => match NabsError::find(&e) {
+                    Some(NabsError::UnreadableFile { path, .. }) => self.warn(Warning {
+                        kind: WarningKind::UnreadablePackage,
+                        package: s.name.to_string(),
+                        subject: s.name.to_string(),
+                        reason: format!("{:?}", e),
+                        location: Some(Location::file(path)),
+                    }),
+                    _ => return Err(e),
+                },
+            }
         }
+        let stats = self.memo_stats();
+        info!(
+            "inferred {} packages, reused results {} times",
+            stats.misses, stats.hits
+        );
         Ok((g, our_targets))
     }
 
@@ -58,33 +347,50 @@ impl InferRunner {
     // we also need to add an edge for us and them
     // so we need to return the nodes that resulted from us being inserted
     // there can be multiple nodes
-    fn build_graph_rec(&self, g: &mut TargetGraph, raw: &RawTarget) -> Result<Vec<Target>> {
+    fn build_graph_rec(
+        &self,
+        g: &mut TargetGraph,
+        raw: &RawTarget,
+        excluded: &HashSet<RawTarget>,
+    ) -> Result<Vec<Target>> {
         // if our inference fails, we return fast
-        let our_inferred_targets = self.run_inf(raw)?;
-        for our in our_inferred_targets.iter() {
+        let memoized = self.memoized_inf(raw);
+        let our_inferred_targets = match memoized.as_ref() {
+            Ok(singles) => singles,
+            Err(failure) => return Err(failure.to_error()),
+        };
+        for LinkedSingle {
+            single: our,
+            linked,
+        } in our_inferred_targets.iter()
+        {
             // for one of our targets, we need to build graph of parents
             if g.contains_node(&our.target) {
                 continue;
             }
             g.add_node(our.target.clone());
             self.warn_for_failed_parents(&our.target.name_as_string_ref(), &our.failed_parents);
-            for p in &our.parents {
+            let declared = our.parents.iter().map(|p| (p, EdgeKind::Declared));
+            let linked = linked.iter().map(|(p, kind)| (p, *kind));
+            for (p, kind) in declared
+                .chain(linked)
+                .filter(|(p, _)| !excluded.contains(p))
+            {
                 // for a parent's failure in inference, currently only logging it
                 // the cli would ignore failures in parent graph building
                 // this at-least gives us a partial graph, terminated at the point of failure
 
-                match self.build_graph_rec(g, p) {
-                    Err(e) => {
-                        eprintln!(
-                            "warning: failed in creating graph for package={}. nabs will skip adding this target in analysis. This package was found in the dependencies of {}",
-                            p.name,
-                            our.target.name,
-                        );
-                        eprintln!("reason:\n{:?}", e);
-                    }
+                match self.build_graph_rec(g, p, excluded) {
+                    Err(e) => self.warn(Warning {
+                        kind: WarningKind::ParentInferenceFailed,
+                        package: our.target.name.to_string(),
+                        subject: p.name.to_string(),
+                        reason: format!("{:?}", e),
+                        location: None,
+                    }),
                     Ok(parent_targets) => {
                         for pt in parent_targets {
-                            g.add_edge(&pt, &our.target).expect(
+                            g.add_edge(&pt, &our.target, kind).expect(
                                 &format!("unexpected corruption, failed in adding edge for {:?} and {:?} even though they should be in the graph", p, our.target)
                             );
                         }
@@ -92,19 +398,88 @@ impl InferRunner {
                 };
             }
         }
-        Ok(our_inferred_targets.into_iter().map(|i| i.target).collect())
+        Ok(our_inferred_targets
+            .iter()
+            .map(|i| i.single.target.clone())
+            .collect())
+    }
+
+    /// runs inference for `start` and everything they depend on, using a thread pool
+    /// packages are inferred in waves, the parents found in one wave are inferred in the next one
+    fn infer_all(&self, start: &[RawTarget], excluded: &HashSet<RawTarget>) {
+        let mut seen: HashSet<RawTarget> = excluded.clone();
+        let mut wave: Vec<RawTarget> = start
+            .iter()
+            .filter(|s| seen.insert((*s).clone()))
+            .cloned()
+            .collect();
+        while !wave.is_empty() {
+            let results: Vec<Arc<Memoized>> = wave
+                .into_par_iter()
+                .map(|raw| self.memoized_inf(&raw))
+                .collect();
+            wave = Vec::new();
+            for memoized in results {
+                for s in memoized.as_ref().iter().flatten() {
+                    let declared = s.single.parents.iter();
+                    let linked = s.linked.iter().map(|(p, _)| p);
+                    for p in declared.chain(linked) {
+                        if seen.insert(p.clone()) {
+                            wave.push(p.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// `run_inf`, but only the first call for a package runs inference
+    /// the table is not locked while inferring, packages are inferred in parallel by `infer_all`
+    fn memoized_inf(&self, raw: &RawTarget) -> Arc<Memoized> {
+        if let Some(m) = self.memo.lock().unwrap().get(raw) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Arc::clone(m);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let memoized = Arc::new(self.run_inf(raw).map_err(|e| Failure::new(&e)));
+        Arc::clone(
+            self.memo
+                .lock()
+                .unwrap()
+                .entry(raw.clone())
+                .or_insert(memoized),
+        )
     }
 
     fn warn_for_failed_parents(&self, target_name: &str, failed: &Vec<FailedParent>) {
         for p in failed {
-            eprintln!(
-                "warn: failed parsing dependency for package={} dependency={}, reason={}",
-                target_name, p.name, p.reason
-            );
+            self.warn(Warning {
+                kind: WarningKind::FailedParent,
+                package: target_name.to_string(),
+                subject: p.name.clone(),
+                reason: p.reason.clone(),
+                location: p.location.clone(),
+            });
         }
     }
 
-    pub fn run_inf(&self, raw: &RawTarget) -> Result<Vec<Single>> {
+    fn run_inf(&self, raw: &RawTarget) -> Result<Vec<LinkedSingle>> {
+        let singles = match &self.cache {
+            None => self.infer_singles(raw)?,
+            Some(cache) => match cache.get(raw) {
+                Some(singles) => singles,
+                None => {
+                    let (singles, inputs) = recording(|| self.infer_singles(raw));
+                    let singles = singles?;
+                    cache.put(raw, inputs, &singles);
+                    singles
+                }
+            },
+        };
+        self.add_linked_parents(raw, singles)
+    }
+
+    fn infer_singles(&self, raw: &RawTarget) -> Result<Vec<Single>> {
         // a single infer can return 0, 1 or more targets
         // we run multiple infers in a list
         // it is invalid for multiple infers to return anything other than 0
@@ -117,16 +492,42 @@ impl InferRunner {
         self.validate_inferred_targets(raw, &inferred_targets)?;
 
         let t = std::mem::replace(&mut inferred_targets[0], InferredTarget::Nothing);
-        match t {
+        let singles = match t {
             InferredTarget::Nothing => {
                 panic!(
                     "inferred_targets is a list with only `Nothing` inside, this is impossible, package={}",
                     raw.name
                 );
             }
-            InferredTarget::One(s) => Ok(vec![s]),
-            InferredTarget::Many(m) => Ok(m),
+            InferredTarget::One(s) => vec![s],
+            InferredTarget::Many(m) => m,
+        };
+        Ok(singles)
+    }
+
+    fn add_linked_parents(
+        &self,
+        raw: &RawTarget,
+        singles: Vec<Single>,
+    ) -> Result<Vec<LinkedSingle>> {
+        let mut singles: Vec<LinkedSingle> = singles
+            .into_iter()
+            .map(|single| LinkedSingle {
+                single,
+                linked: Vec::new(),
+            })
+            .collect();
+        for link in &self.links {
+            let (success, failed) = link
+                .extra_parents(raw)
+                .context("failed in finding linked parents")?;
+            for s in singles.iter_mut() {
+                s.linked
+                    .extend(success.iter().map(|p| (p.clone(), link.edge_kind())));
+                s.single.failed_parents.extend(failed.iter().cloned());
+            }
         }
+        Ok(singles)
     }
 
     fn raw_run_inferrers(&self, raw: &RawTarget) -> Result<Vec<InferredTarget>> {
@@ -137,23 +538,9 @@ impl InferRunner {
         // an infer can also say if we want to infer more after giving some result
         // the first infer which directly reads nabs.json simply asks us to break if it finds any target
         // basically, if you want to make sure nobody infers after you, you return break and its guaranteed that your infer would work
-        let mut inferred_targets = Vec::new();
-        for inf in &self.infers {
-            let inf_res = inf
-                .from_raw_target(raw)
-                .context("failed in building graph of targets")?;
-            if let InferredTarget::Nothing = inf_res.inferred_target {
-                // nothing, just want the else part
-            } else {
-                inferred_targets.push(inf_res.inferred_target);
-            }
-
-            match inf_res.what_next {
-                Next::Break => {
-                    break;
-                }
-                Next::Continue => {}
-            };
+        let mut inferred_targets = run_inferrers(&self.infers, raw)?;
+        if inferred_targets.is_empty() {
+            inferred_targets = run_inferrers(&self.fallbacks, raw)?;
         }
         Ok(inferred_targets)
     }
@@ -163,37 +550,74 @@ impl InferRunner {
         raw: &RawTarget,
         inferred_targets: &Vec<InferredTarget>,
     ) -> Result<()> {
-        // TODO: if i want people to use this application
-        // will need to return concrete error types here so that we can handle this at top level and show a good message
         if inferred_targets.len() > 1 {
-            bail!(
-                "err: found a target where multiple build systems were inferred. this is not allowed for automatic inference. You can add entries manually for each target in nabs.json for the package, package_path={:} inferred_targets={:?}",
-                raw.name,
-                inferred_targets
-            );
+            let flavors = inferred_targets
+                .iter()
+                .flat_map(|t| match t {
+                    InferredTarget::One(single) => vec![single.target.flavor.clone()],
+                    InferredTarget::Many(singles) => {
+                        singles.iter().map(|s| s.target.flavor.clone()).collect()
+                    }
+                    InferredTarget::Nothing => vec![],
+                })
+                .collect();
+            return Err(NabsError::AmbiguousInference {
+                package: raw.name.to_string(),
+                flavors,
+            }
+            .into());
         }
         if inferred_targets.len() == 0 {
-            bail!(
-                "err: could not infer any target for package={}",
-                raw.name
-            );
+            return Err(NabsError::NothingInferred {
+                package: raw.name.to_string(),
+            }
+            .into());
         }
         Ok(())
     }
 }
 
+fn run_inferrers(infers: &[Box<dyn Infer>], raw: &RawTarget) -> Result<Vec<InferredTarget>> {
+    let mut inferred_targets = Vec::new();
+    for inf in infers {
+        let inf_res = inf
+            .from_raw_target(raw)
+            .context("failed in building graph of targets")?;
+        if let InferredTarget::Nothing = inf_res.inferred_target {
+            // nothing, just want the else part
+        } else {
+            inferred_targets.push(inf_res.inferred_target);
+        }
+
+        match inf_res.what_next {
+            Next::Break => {
+                break;
+            }
+            Next::Continue => {}
+        };
+    }
+    Ok(inferred_targets)
+}
+
 #[cfg(test)]
 mod test {
     // this is going to be slightly complicated, ill need to create test structs inferrers also
     // these would actually be pretty simple structs
     // given an instantiated struct, they just be a list of what the dependencies are, we pass the map in instantiation
 
-    use std::collections::HashMap;
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
 
     use crate::{
+        config::WorkspaceConfig,
+        error::NabsError,
         graph::TargetGraph,
         infer::InferRunner,
-        types::{RawTarget, Target},
+        types::{MockRepo, RawTarget, Repository, Target},
+        warning::WarningKind,
     };
 
     use super::core::{Infer, InferResult, InferredTarget, Next, Single};
@@ -288,6 +712,214 @@ mod test {
         compare(&graph, "image_manager", "python", vec![]);
     }
 
+    #[test]
+    fn test_runner_parallel_matches_sequential() {
+        let start = || {
+            [
+                "qureapi",
+                "cathode",
+                "qxr_reports",
+                "qer_reports",
+                "qure_dicom_utils",
+            ]
+            .map(|n| RawTarget::from_string_name(n.to_string()).unwrap())
+        };
+        let render = |(graph, targets): (TargetGraph, Vec<Target>)| {
+            let names: Vec<String> = targets
+                .iter()
+                .map(|t| t.name_as_string_ref().clone())
+                .collect();
+            (format!("{}", graph), names)
+        };
+        // the memo outlives a graph, every run needs its own runner to actually infer the packages
+        let run = || {
+            let runner = InferRunner::new(vec![Box::new(get_infer_1())]);
+            let rendered = render(runner.build_graph(start()).unwrap());
+            (rendered, runner.memo_stats().misses)
+        };
+        let (sequential, misses) = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(run);
+        assert!(misses > 0);
+        for _ in 0..10 {
+            assert_eq!(run(), (sequential.clone(), misses));
+        }
+    }
+
+    struct UnreadableInfer;
+
+    impl Infer for UnreadableInfer {
+        fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+            if t.name.to_string_ref() == "broken" {
+                return Err(NabsError::UnreadableFile {
+                    path: PathBuf::from("broken/Cargo.toml"),
+                    message: "permission denied".to_string(),
+                }
+                .into());
+            }
+            Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            })
+        }
+    }
+
+    #[test]
+    fn test_runner_skips_unreadable() {
+        let infs: Vec<Box<dyn Infer>> = vec![Box::new(UnreadableInfer), Box::new(get_infer_1())];
+        let runner = InferRunner::new(infs);
+        let start = vec![
+            RawTarget::from_string_name("broken".to_string()).unwrap(),
+            RawTarget::from_string_name("qxr".to_string()).unwrap(),
+        ];
+        let (graph, our_targets) = runner.build_graph(start).unwrap();
+        let names: Vec<&String> = our_targets.iter().map(|t| t.name_as_string_ref()).collect();
+        assert_eq!(names, vec!["qxr"]);
+        compare(&graph, "qure_dicom_utils", "cargo", vec![("qxr", "cargo")]);
+
+        // anything other than an unreadable file still fails
+        let runner = InferRunner::new(vec![Box::new(UnreadableInfer)]);
+        let start = vec![RawTarget::from_string_name("qxr".to_string()).unwrap()];
+        assert!(runner.build_graph(start).is_err());
+    }
+
+    /// counts how many times each package is inferred, `broken` can't be read
+    struct CountingInfer {
+        inner: MockInfer,
+        calls: Mutex<HashMap<String, usize>>,
+    }
+
+    impl Infer for CountingInfer {
+        fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+            *self
+                .calls
+                .lock()
+                .unwrap()
+                .entry(t.name.to_string())
+                .or_default() += 1;
+            UnreadableInfer.from_raw_target(t)?;
+            self.inner.from_raw_target(t)
+        }
+    }
+
+    #[test]
+    fn test_runner_memoizes() {
+        let infer = Arc::new(CountingInfer {
+            inner: get_infer_1(),
+            calls: Mutex::new(HashMap::new()),
+        });
+        let runner = InferRunner::new(vec![Box::new(SharedInfer(Arc::clone(&infer)))]);
+        let start = || {
+            ["broken", "qureapi", "cathode", "broken"]
+                .map(|n| RawTarget::from_string_name(n.to_string()).unwrap())
+        };
+        runner.build_graph(start()).unwrap();
+        runner.build_graph(start()).unwrap();
+
+        let calls = infer.calls.lock().unwrap();
+        assert_eq!(calls.len(), 8);
+        assert!(calls.values().all(|n| *n == 1));
+        let stats = runner.memo_stats();
+        assert_eq!(stats.misses, 8);
+        assert!(stats.hits > 8);
+
+        // the failure is the same every time, including the error it was caused by
+        let warnings = runner.warnings();
+        assert_eq!(warnings.len(), 4);
+        for w in &warnings {
+            assert_eq!(w.kind, WarningKind::UnreadablePackage);
+            assert!(w.reason.starts_with(
+                "failed in building graph of targets\n\nCaused by:\n    failed in reading file=\"broken/Cargo.toml\""
+            ));
+            assert!(w.location.is_some());
+        }
+    }
+
+    #[test]
+    fn test_default_runner_colocated_manifests() {
+        // a crate which vendors build files of other build systems is still a crate
+        let fake: HashMap<String, String> = [
+            ("a/nabs.json", ""),
+            ("a/Cargo.toml", "[package]\nname = \"a\""),
+            ("b/nabs.json", ""),
+            ("b/Cargo.toml", "[dependencies]\na = { path = \"../a\" }"),
+            ("b/CMakeLists.txt", "add_subdirectory(../a a_build)"),
+            ("b/BUILD.bazel", "rust_library(deps = [\"//a\"])"),
+            ("b/Gemfile", "gem \"a\", path: \"../a\""),
+            ("b/default.nix", "import ../a/default.nix { }"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(fake, PathBuf::new()));
+        let start = || ["a", "b"].map(|n| RawTarget::from_string_name(n.to_string()).unwrap());
+
+        let runner = InferRunner::default(&repo, &WorkspaceConfig::default()).unwrap();
+        let (graph, _) = runner.build_graph(start()).unwrap();
+        compare(&graph, "a", "cargo", vec![("b", "cargo")]);
+        assert!(runner.warnings().is_empty());
+
+        // once turned on, the package has two flavors, which is ambiguous
+        let configs = [
+            r#"{"enable": {"cmake": true}}"#,
+            r#"{"enable": {"bazel": true}}"#,
+            r#"{"enable": {"gemfile": true}}"#,
+            r#"{"enable": {"nix": true}}"#,
+        ];
+        for config in configs {
+            let config: WorkspaceConfig = serde_json::from_str(config).unwrap();
+            let runner = InferRunner::default(&repo, &config).unwrap();
+            let Err(err) = runner.build_graph(start()) else {
+                panic!("expected b to be ambiguous with config={:?}", config);
+            };
+            assert!(matches!(
+                NabsError::find(&err),
+                Some(NabsError::AmbiguousInference { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_default_runner_python_sources() {
+        // neither package declares anything, they are only linked by their imports
+        let fake: HashMap<String, String> = [
+            ("libs/qsync/nabs.json", ""),
+            ("libs/qsync/qsync/__init__.py", ""),
+            ("apps/api/nabs.json", ""),
+            ("apps/api/main.py", "import qsync"),
+            ("tools/nabs.json", ""),
+            ("tools/Cargo.toml", "[package]\nname = \"tools\""),
+            ("tools/gen.py", "import qsync"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(fake, PathBuf::new()));
+        let config: WorkspaceConfig =
+            serde_json::from_str(r#"{"python": {"scan_imports": true}}"#).unwrap();
+        let runner = InferRunner::default(&repo, &config).unwrap();
+        let start = ["libs/qsync", "apps/api", "tools"]
+            .map(|n| RawTarget::from_string_name(n.to_string()).unwrap());
+        let (graph, _) = runner.build_graph(start).unwrap();
+        compare(
+            &graph,
+            "libs/qsync",
+            "python",
+            vec![("apps/api", "python"), ("tools", "cargo")],
+        );
+        assert!(runner.warnings().is_empty());
+    }
+
+    struct SharedInfer(Arc<CountingInfer>);
+
+    impl Infer for SharedInfer {
+        fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+            self.0.from_raw_target(t)
+        }
+    }
+
     fn compare(graph: &TargetGraph, name: &str, flavor: &str, want: Vec<(&str, &str)>) {
         let ns = graph
             .neighbors(&Target::from_string_name(name.to_string(), flavor.to_string()).unwrap())
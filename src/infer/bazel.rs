@@ -0,0 +1,406 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, anyhow};
+use log::info;
+
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target, TargetName};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+};
+
+pub const BAZEL_FLAVOR: &str = "bazel";
+/// bazel prefers `BUILD.bazel` if both files are present in a package
+pub const BUILD_FILE_NAMES: [&str; 2] = ["BUILD.bazel", "BUILD"];
+pub const MODULE_FILE_NAME: &str = "MODULE.bazel";
+
+/// attributes of a rule which point to other targets we depend on
+const DEP_ATTRIBUTES: [&str; 3] = ["deps", "runtime_deps", "exports"];
+
+/// infers a bazel package by statically reading its `BUILD`/`BUILD.bazel` and `MODULE.bazel` files
+/// the bazel workspace root is assumed to be the same as the nabs workspace root
+/// this lets us map labels directly, `//libs/foo:bar` lives in the bazel package `libs/foo`, which is the `TargetName` `libs/foo`
+/// the bazel package can be a sub-directory of a nabs package, the dependency is the nabs package enclosing it
+///
+/// - `BUILD`: string labels inside list literals of `deps`, `runtime_deps` and `exports` are used
+///   lists inside `select()` are not evaluated, external labels (`@repo//...`) and labels in our own package (`:bar`) are ignored
+/// - `MODULE.bazel`: `local_path_override(path = "../foo")` is resolved relative to the package
+pub struct BazelInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl BazelInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+
+    /// path and content of the `BUILD` file
+    fn get_build_file(&self, t: &RawTarget) -> anyhow::Result<Option<(PathBuf, String)>> {
+        let base = self.repo.target_name_to_path(&t.name);
+        for name in BUILD_FILE_NAMES {
+            let path = base.join(name);
+            if let Some(content) = self.repo.get_content(&path)? {
+                return Ok(Some((path, content)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// path and content of the `MODULE.bazel` file
+    fn get_module_file(&self, t: &RawTarget) -> anyhow::Result<Option<(PathBuf, String)>> {
+        let path = self
+            .repo
+            .target_name_to_path(&t.name)
+            .join(MODULE_FILE_NAME);
+        Ok(self.repo.get_content(&path)?.map(|content| (path, content)))
+    }
+
+    fn label_to_package(&self, label: &str) -> Result<Option<RawTarget>, FailedParent> {
+        let Some(pkg) = label_package(label) else {
+            return Ok(None);
+        };
+        let failed = |reason: String| FailedParent {
+            name: label.to_string(),
+            reason,
+            location: None,
+        };
+        if pkg.is_empty() {
+            return Err(failed(
+                "labels in the root package cannot be mapped to a nabs package".to_string(),
+            ));
+        }
+        let name = TargetName::new(pkg.to_string()).map_err(|e| failed(format!("{}", e)))?;
+        match self.repo.enclosing_package(&RawTarget::new(name)) {
+            None => Err(failed(format!(
+                "bazel package={} is not part of any package",
+                pkg
+            ))),
+            Some(p) => Ok(Some(p)),
+        }
+    }
+
+    fn override_to_package(&self, t: &RawTarget, path: String) -> Result<RawTarget, FailedParent> {
+        let path = BuildSystemPath::new(path, PathFormat::Posix);
+        let failed = |reason: String| FailedParent {
+            name: path.raw.clone(),
+            reason,
+            location: None,
+        };
+        if path.is_absolute() {
+            return Err(failed("absolute paths are not allowed".to_string()));
+        }
+        let resolved = self
+            .repo
+            .resolve_rel_path(&path, t)
+            .map_err(|e| failed(format!("{}", e)))?;
+        self.repo
+            .enclosing_package(&resolved)
+            .ok_or_else(|| failed(format!("directory={} is not part of any package", resolved)))
+    }
+
+    fn get_parents(
+        &self,
+        t: &RawTarget,
+        build: Option<&(PathBuf, String)>,
+        module: Option<&(PathBuf, String)>,
+    ) -> (Vec<RawTarget>, Vec<FailedParent>) {
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        if let Some((file, content)) = build {
+            let before = failed.len();
+            for label in get_dep_labels(&tokenize(content)) {
+                if let Some(r) = self.label_to_package(&label).transpose() {
+                    add_result(t, r, &mut success, &mut failed);
+                }
+            }
+            locate_failed_parents(&mut failed[before..], file, content);
+        }
+        if let Some((file, content)) = module {
+            let before = failed.len();
+            for path in get_local_path_overrides(&tokenize(content)) {
+                let r = self.override_to_package(t, path);
+                add_result(t, r, &mut success, &mut failed);
+            }
+            locate_failed_parents(&mut failed[before..], file, content);
+        }
+        (success, failed)
+    }
+}
+
+impl Infer for BazelInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        let mut files: Vec<String> = BUILD_FILE_NAMES.iter().map(|n| n.to_string()).collect();
+        files.push(MODULE_FILE_NAME.to_string());
+        Some(files)
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let build = self.get_build_file(t)?;
+        let module = self.get_module_file(t)?;
+        if build.is_none() && module.is_none() {
+            return Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            });
+        }
+
+        let (success, failed) = self.get_parents(t, build.as_ref(), module.as_ref());
+        info!("BazelInfer: detected package={}", t);
+        Ok(InferResult {
+            inferred_target: InferredTarget::One(Single {
+                target: Target::from_raw_target(t, BAZEL_FLAVOR.to_string())
+                    .context(anyhow!("failed in creating target for package={}", t.name))?,
+                parents: success,
+                failed_parents: failed,
+            }),
+            what_next: Next::Continue,
+        })
+    }
+}
+
+/// a package we depend on, references to ourselves and duplicates are skipped
+fn add_result(
+    t: &RawTarget,
+    r: Result<RawTarget, FailedParent>,
+    success: &mut Vec<RawTarget>,
+    failed: &mut Vec<FailedParent>,
+) {
+    match r {
+        Ok(p) => {
+            if p != *t && !success.contains(&p) {
+                success.push(p);
+            }
+        }
+        Err(f) => failed.push(f),
+    }
+}
+
+/// the bazel package path of a label, without the leading `//`
+/// `None` if the label does not point to another package in our workspace (external repositories, relative labels)
+fn label_package(label: &str) -> Option<&str> {
+    // `@//` and `@@//` are the main repository, anything else starting with `@` is an external repository
+    let rest = label.trim_start_matches('@').strip_prefix("//")?;
+    Some(rest.split_once(':').map_or(rest, |(pkg, _)| pkg))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+/// a tokenizer for the subset of starlark we care about
+/// comments are dropped, numbers and operators are all returned as single characters
+fn tokenize(content: &str) -> Vec<Token> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                }
+                s.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(Token::Str(s));
+            i += 1;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            if !c.is_whitespace() {
+                tokens.push(Token::Punct(c));
+            }
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// all string labels inside `<dep attribute> = [...]` list literals
+fn get_dep_labels(tokens: &[Token]) -> Vec<String> {
+    let mut labels = Vec::new();
+    for i in 0..tokens.len() {
+        let is_dep_attr =
+            matches!(&tokens[i], Token::Ident(name) if DEP_ATTRIBUTES.contains(&name.as_str()));
+        if !is_dep_attr
+            || tokens.get(i + 1) != Some(&Token::Punct('='))
+            || tokens.get(i + 2) != Some(&Token::Punct('['))
+        {
+            continue;
+        }
+        let mut depth = 0;
+        for tok in &tokens[i + 2..] {
+            match tok {
+                Token::Punct('[') | Token::Punct('(') | Token::Punct('{') => depth += 1,
+                Token::Punct(']') | Token::Punct(')') | Token::Punct('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                // nested structures are calls or comprehensions, we can't evaluate them
+                Token::Str(s) if depth == 1 => labels.push(s.clone()),
+                _ => {}
+            }
+        }
+    }
+    labels
+}
+
+/// the `path` argument of every `local_path_override(...)` call
+fn get_local_path_overrides(tokens: &[Token]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for i in 0..tokens.len() {
+        if tokens[i] != Token::Ident("local_path_override".to_string())
+            || tokens.get(i + 1) != Some(&Token::Punct('('))
+        {
+            continue;
+        }
+        let mut depth = 0;
+        for (j, tok) in tokens.iter().enumerate().skip(i + 1) {
+            match tok {
+                Token::Punct('(') => depth += 1,
+                Token::Punct(')') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                Token::Ident(name)
+                    if depth == 1
+                        && name == "path"
+                        && tokens.get(j + 1) == Some(&Token::Punct('=')) =>
+                {
+                    if let Some(Token::Str(p)) = tokens.get(j + 2) {
+                        paths.push(p.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::BazelInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "services/api";
+        let build_str = r#"
+            load("@rules_python//python:defs.bzl", "py_binary")
+
+            py_binary(
+                name = "api",
+                srcs = ["main.py"],
+                deps = [
+                    ":handlers",
+                    "//libs/auth:auth",
+                    "//libs/db/postgres:client",  # nested bazel package
+                    "@pypi//requests",
+                    "@//libs/metrics",
+                    "//:root_target",
+                    "//third_party/unknown:lib",
+                ] + select({
+                    "//conditions:default": [],
+                }),
+            )
+
+            py_library(
+                name = "handlers",
+                deps = ["//libs/auth"],
+                runtime_deps = ['//services/api/plugins:all'],
+            )
+        "#;
+        let module_str = r#"
+            module(name = "api")
+            bazel_dep(name = "shared_proto", version = "1.0")
+            local_path_override(
+                module_name = "shared_proto",
+                path = "../../proto/shared",
+            )
+            local_path_override(module_name = "escape", path = "../../../escape")
+        "#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/BUILD.bazel", us_name), build_str.to_string()),
+                (format!("{}/MODULE.bazel", us_name), module_str.to_string()),
+                (format!("{}/nabs.json", us_name), "{}".to_string()),
+                ("libs/auth/nabs.json".to_string(), "{}".to_string()),
+                ("libs/db/nabs.json".to_string(), "{}".to_string()),
+                ("libs/metrics/nabs.json".to_string(), "{}".to_string()),
+                ("proto/shared/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let inf = BazelInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[
+                    &"libs/auth".to_string(),
+                    &"libs/db".to_string(),
+                    &"libs/metrics".to_string(),
+                    &"proto/shared".to_string(),
+                ],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(
+                &failed_parents,
+                &[
+                    "//:root_target".to_string(),
+                    "//third_party/unknown:lib".to_string(),
+                    "../../../escape".to_string(),
+                ],
+            );
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
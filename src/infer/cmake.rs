@@ -0,0 +1,414 @@
+use std::sync::Arc;
+
+use anyhow::{Context, anyhow};
+use log::info;
+
+use crate::error::NabsError;
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+};
+
+pub const CMAKE_FLAVOR: &str = "cmake";
+pub const CMAKE_LISTS_FILE_NAME: &str = "CMakeLists.txt";
+pub const CONAN_WORKSPACE_FILE_NAME: &str = "conanws.yml";
+
+/// infers a cmake package using its `CMakeLists.txt`
+/// two commands are used for finding dependencies
+/// - `add_subdirectory(<source_dir> [binary_dir])`: the source directory is the dependency
+/// - `include(<file>.cmake)`: the directory containing the cmake script is the dependency
+///   `include(SomeModule)` (without the `.cmake` extension) is a module lookup, this is ignored
+///
+/// only literal paths can be resolved statically, paths using variables (like `${CMAKE_CURRENT_SOURCE_DIR}/../common`)
+/// are reported as `FailedParent`
+/// both commands can point to any directory inside another package, the dependency is the package enclosing that directory
+/// references which stay inside our own package (`add_subdirectory(src)`) are not dependencies and are ignored
+///
+/// conan has no path dependencies in `conanfile.py`, local packages are put together in a conan workspace instead
+/// every package listed in `conanws.yml` is a dependency, both the conan 1 and conan 2 layouts are read
+/// ```yaml
+/// editables:
+///   common/0.1:
+///     path: ../common
+/// packages:
+///   - path: ../math
+///     ref: math/0.1
+/// ```
+pub struct CMakeInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl CMakeInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+
+    fn get_parents(
+        &self,
+        t: &RawTarget,
+        dirs: Vec<String>,
+        success: &mut Vec<RawTarget>,
+        failed: &mut Vec<FailedParent>,
+    ) {
+        for dir in dirs {
+            if dir.contains("${") || dir.contains("$ENV{") || dir.contains("$<") {
+                failed.push(FailedParent {
+                    name: dir,
+                    reason:
+                        "paths using cmake variables or generator expressions are not supported"
+                            .to_string(),
+                    location: None,
+                });
+                continue;
+            }
+            let path = BuildSystemPath::new(dir, PathFormat::Posix);
+            if path.is_absolute() {
+                failed.push(FailedParent {
+                    name: path.raw.clone(),
+                    reason: "absolute paths are not allowed".to_string(),
+                    location: None,
+                });
+                continue;
+            }
+            let resolved = match self.repo.resolve_rel_path(&path, t) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    failed.push(FailedParent {
+                        name: path.raw.clone(),
+                        reason: format!("{}", e),
+                        location: None,
+                    });
+                    continue;
+                }
+            };
+            match self.repo.enclosing_package(&resolved) {
+                None => failed.push(FailedParent {
+                    name: path.raw.clone(),
+                    reason: format!("directory={} is not part of any package", resolved),
+                    location: None,
+                }),
+                Some(pkg) => {
+                    if pkg != *t && !success.contains(&pkg) {
+                        success.push(pkg);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Infer for CMakeInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![
+            CMAKE_LISTS_FILE_NAME.to_string(),
+            CONAN_WORKSPACE_FILE_NAME.to_string(),
+        ])
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let dir = self.repo.target_name_to_path(&t.name);
+        let cmake_path = dir.join(CMAKE_LISTS_FILE_NAME);
+        let conan_path = dir.join(CONAN_WORKSPACE_FILE_NAME);
+        let cmake = self.repo.get_content(&cmake_path)?;
+        let conan = self.repo.get_content(&conan_path)?;
+        if cmake.is_none() && conan.is_none() {
+            return Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            });
+        }
+
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        if let Some(content) = cmake {
+            let mut ours = Vec::new();
+            self.get_parents(t, get_referenced_dirs(&content), &mut success, &mut ours);
+            locate_failed_parents(&mut ours, &cmake_path, &content);
+            failed.extend(ours);
+        }
+        if let Some(content) = conan {
+            let workspace: serde_yaml::Value = serde_yaml::from_str(&content)
+                .map_err(|e| NabsError::from_yaml(conan_path.clone(), &e))?;
+            let (dirs, mut ours) = get_conan_dirs(&workspace);
+            self.get_parents(t, dirs, &mut success, &mut ours);
+            locate_failed_parents(&mut ours, &conan_path, &content);
+            failed.extend(ours);
+        }
+        info!("CMakeInfer: detected package={}", t);
+        Ok(InferResult {
+            inferred_target: InferredTarget::One(Single {
+                target: Target::from_raw_target(t, CMAKE_FLAVOR.to_string())
+                    .context(anyhow!("failed in creating target for package={}", t.name))?,
+                parents: success,
+                failed_parents: failed,
+            }),
+            what_next: Next::Continue,
+        })
+    }
+}
+
+/// directories (relative to the `conanws.yml`) of the packages in a conan workspace
+/// `editables` is a map from references to `{path}` in conan 1, `packages` is a list of `{path, ref}` in conan 2
+fn get_conan_dirs(workspace: &serde_yaml::Value) -> (Vec<String>, Vec<FailedParent>) {
+    let editables = workspace["editables"]
+        .as_mapping()
+        .into_iter()
+        .flat_map(|m| m.iter());
+    let packages = workspace["packages"]
+        .as_sequence()
+        .into_iter()
+        .flatten()
+        .map(|p| (&p["ref"], p));
+    let mut dirs = Vec::new();
+    let mut failed = Vec::new();
+    for (reference, package) in editables.chain(packages) {
+        match &package["path"] {
+            serde_yaml::Value::String(path) => dirs.push(path.clone()),
+            _ => failed.push(FailedParent {
+                name: reference.as_str().unwrap_or_default().to_string(),
+                reason: "conan workspace package without a literal `path`".to_string(),
+                location: None,
+            }),
+        }
+    }
+    (dirs, failed)
+}
+
+/// directories (relative to the `CMakeLists.txt`) referenced by `add_subdirectory` and `include`
+fn get_referenced_dirs(content: &str) -> Vec<String> {
+    let mut dirs = Vec::new();
+    for (name, args) in get_commands(content) {
+        let Some(first) = args.into_iter().next() else {
+            continue;
+        };
+        match name.as_str() {
+            "add_subdirectory" => dirs.push(first),
+            "include" if first.ends_with(".cmake") => {
+                // a script in the same directory as us is always part of our package
+                if let Some((dir, _)) = first.rsplit_once('/') {
+                    dirs.push(dir.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    dirs
+}
+
+/// a very small cmake tokenizer, returns every command invocation as (lowercased name, arguments)
+/// handles comments, quoted arguments and nested parenthesis, bracket arguments are not supported
+fn get_commands(content: &str) -> Vec<(String, Vec<String>)> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut commands = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '#' {
+            i = skip_line(&chars, i);
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+                i += 1;
+            }
+            if i < chars.len() && chars[i] == '(' {
+                let (args, end) = get_args(&chars, i + 1);
+                commands.push((name.to_lowercase(), args));
+                i = end;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    commands
+}
+
+/// parses arguments starting right after the opening parenthesis
+/// returns the arguments and the index right after the closing parenthesis
+fn get_args(chars: &[char], mut i: usize) -> (Vec<String>, usize) {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut depth = 1;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '#' => {
+                i = skip_line(chars, i);
+                continue;
+            }
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                    }
+                    cur.push(chars[i]);
+                    i += 1;
+                }
+            }
+            '(' => {
+                depth += 1;
+                cur.push(c);
+            }
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    i += 1;
+                    break;
+                }
+                cur.push(c);
+            }
+            c if c.is_whitespace() => {
+                if !cur.is_empty() {
+                    args.push(std::mem::take(&mut cur));
+                }
+            }
+            c => cur.push(c),
+        }
+        i += 1;
+    }
+    if !cur.is_empty() {
+        args.push(cur);
+    }
+    (args, i)
+}
+
+fn skip_line(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::CMakeInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "native/engine";
+        let cmake_str = r#"
+            cmake_minimum_required(VERSION 3.20)
+            project(engine CXX)
+
+            add_subdirectory(src)
+            add_subdirectory(../common common_build)
+            ADD_SUBDIRECTORY("../math/core" math_build) # nested directory of a package
+            # add_subdirectory(../commented_out)
+            add_subdirectory(${CMAKE_CURRENT_SOURCE_DIR}/../generated)
+            add_subdirectory(../../../../outside)
+            add_subdirectory(../not_a_package)
+
+            include(CTest)
+            include(../toolchain/cmake/warnings.cmake)
+            include(local.cmake)
+        "#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/CMakeLists.txt", us_name), cmake_str.to_string()),
+                (format!("{}/nabs.json", us_name), "{}".to_string()),
+                ("native/common/nabs.json".to_string(), "{}".to_string()),
+                ("native/math/nabs.json".to_string(), "{}".to_string()),
+                ("native/toolchain/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let inf = CMakeInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[
+                    &"native/common".to_string(),
+                    &"native/math".to_string(),
+                    &"native/toolchain".to_string(),
+                ],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(
+                &failed_parents,
+                &[
+                    "${CMAKE_CURRENT_SOURCE_DIR}/../generated".to_string(),
+                    "../../../../outside".to_string(),
+                    "../not_a_package".to_string(),
+                ],
+            );
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    #[test]
+    fn test_conan_workspace() {
+        let conan_str = r#"
+editables:
+  common/0.1@corp/stable:
+    path: ../common
+  generated/0.1:
+    layout: layout_gcc
+packages:
+  - path: ../math/core
+    ref: math/0.1
+"#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                ("native/app/conanws.yml".to_string(), conan_str.to_string()),
+                ("native/app/nabs.json".to_string(), "{}".to_string()),
+                ("native/common/nabs.json".to_string(), "{}".to_string()),
+                ("native/math/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let inf = CMakeInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name("native/app".to_string()).unwrap())
+            .unwrap();
+        let InferredTarget::One(single) = infer_result.inferred_target else {
+            panic!("expected inferred_target to be One variant");
+        };
+        let parents: Vec<&String> = single
+            .parents
+            .iter()
+            .map(|p| p.name.to_string_ref())
+            .collect();
+        compare_vec(
+            &parents,
+            &[&"native/common".to_string(), &"native/math".to_string()],
+        );
+        assert_eq!(single.failed_parents.len(), 1);
+        assert_eq!(single.failed_parents[0].name, "generated/0.1");
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use anyhow::{Context, anyhow};
+use log::info;
+use serde::Deserialize;
+
+use crate::error::NabsError;
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+    resolve_parent,
+};
+
+pub const COMPOSER_FLAVOR: &str = "php_composer";
+pub const COMPOSER_FILE_NAME: &str = "composer.json";
+
+#[derive(Debug, Deserialize)]
+struct ComposerRepository {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+/// composer allows `repositories` to be both a list and a map of name to repository
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Repositories {
+    List(Vec<serde_json::Value>),
+    Map(serde_json::Map<String, serde_json::Value>),
+}
+
+impl Default for Repositories {
+    fn default() -> Self {
+        Repositories::List(Vec::new())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ComposerJson {
+    #[serde(default)]
+    repositories: Repositories,
+}
+
+/// infers a php package using its `composer.json`
+/// local packages are declared as path repositories `{"type": "path", "url": "../x"}`
+/// every path repository is treated as a dependency, even if nothing in `require` uses it
+/// composer allows wildcards in the url (`../packages/*`), these are reported as `FailedParent`
+pub struct ComposerInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl ComposerInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+}
+
+impl Infer for ComposerInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![COMPOSER_FILE_NAME.to_string()])
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let path = self
+            .repo
+            .target_name_to_path(&t.name)
+            .join(COMPOSER_FILE_NAME);
+        let content = self.repo.get_content(&path)?;
+
+        match content {
+            None => Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            }),
+            Some(content) => {
+                let composer: ComposerJson = serde_json::from_str(&content)
+                    .map_err(|e| NabsError::from_json(path.clone(), &e))?;
+                let mut success = Vec::new();
+                let mut failed = Vec::new();
+                for url in get_path_urls(composer.repositories) {
+                    if url.contains(['*', '?', '[', '{']) {
+                        failed.push(FailedParent {
+                            name: url,
+                            reason: "wildcards in path repositories are not supported".to_string(),
+                            location: None,
+                        });
+                        continue;
+                    }
+                    let p = BuildSystemPath::new(url, PathFormat::Posix);
+                    match resolve_parent(self.repo.as_ref(), &p, t) {
+                        Ok(raw_target) => success.push(raw_target),
+                        Err(f) => failed.push(f),
+                    }
+                }
+                locate_failed_parents(&mut failed, &path, &content);
+                info!("ComposerInfer: detected package={}", t);
+                Ok(InferResult {
+                    inferred_target: InferredTarget::One(Single {
+                        target: Target::from_raw_target(t, COMPOSER_FLAVOR.to_string())
+                            .context(anyhow!("failed in creating target for package={}", t.name))?,
+                        parents: success,
+                        failed_parents: failed,
+                    }),
+                    what_next: Next::Continue,
+                })
+            }
+        }
+    }
+}
+
+fn get_path_urls(repositories: Repositories) -> Vec<String> {
+    let values: Vec<serde_json::Value> = match repositories {
+        Repositories::List(l) => l,
+        Repositories::Map(m) => m.into_iter().map(|(_, v)| v).collect(),
+    };
+    values
+        .into_iter()
+        // entries like `{"packagist.org": false}` are not repositories, skip them
+        .filter_map(|v| serde_json::from_value::<ComposerRepository>(v).ok())
+        .filter(|r| r.kind == "path")
+        .filter_map(|r| r.url)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::ComposerInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "legacy/shop";
+        let composer_str = r#"
+            {
+                "name": "acme/shop",
+                "repositories": [
+                    {"type": "path", "url": "../cart"},
+                    {"type": "path", "url": "../../libs/php-auth", "options": {"symlink": true}},
+                    {"type": "vcs", "url": "https://github.com/acme/thing"},
+                    {"type": "path", "url": "../packages/*"},
+                    {"type": "path", "url": "../../../outside"},
+                    {"packagist.org": false}
+                ],
+                "require": {"acme/cart": "*"}
+            }
+        "#;
+        let repo = MockRepo::new(
+            HashMap::from([(
+                format!("{}/composer.json", us_name),
+                composer_str.to_string(),
+            )]),
+            PathBuf::new(),
+        );
+        let inf = ComposerInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[&"legacy/cart".to_string(), &"libs/php-auth".to_string()],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(
+                &failed_parents,
+                &["../packages/*".to_string(), "../../../outside".to_string()],
+            );
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
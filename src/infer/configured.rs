@@ -0,0 +1,231 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::{Context, Result, anyhow, bail};
+use globset::{Glob, GlobMatcher};
+use log::info;
+use regex::Regex;
+
+use crate::config::InferrerConfig;
+use crate::types::{BuildSystemPath, RawTarget, Repository, Target};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+    resolve_parent,
+};
+
+const PATH_GROUP_NAME: &str = "path";
+
+/// an inferrer declared in the `inferrers` list of `workspace.json`
+/// this is for build systems nabs does not support, where dependencies are paths written in a manifest
+///
+/// a package is detected if any file in it matches the `manifest` glob (relative to the package directory)
+/// every match of `pattern` in those manifests is a path, relative to the manifest's own directory
+/// a path can point anywhere inside a package, the dependency is the package enclosing it
+pub struct ConfiguredInfer {
+    repo: Arc<dyn Repository>,
+    manifest: GlobMatcher,
+    pattern: Regex,
+    config: InferrerConfig,
+}
+
+impl ConfiguredInfer {
+    pub fn new(repo: Arc<dyn Repository>, config: InferrerConfig) -> Result<Self> {
+        let manifest = Glob::new(&config.manifest)
+            .context(anyhow!(
+                "invalid manifest glob for inferrer flavor={}",
+                config.flavor
+            ))?
+            .compile_matcher();
+        let pattern = Regex::new(&config.pattern).context(anyhow!(
+            "invalid pattern for inferrer flavor={}",
+            config.flavor
+        ))?;
+        if pattern.captures_len() < 2 {
+            bail!(
+                "pattern for inferrer flavor={} has no capture group, pattern={}",
+                config.flavor,
+                config.pattern
+            );
+        }
+        Ok(Self {
+            repo,
+            manifest,
+            pattern,
+            config,
+        })
+    }
+
+    fn get_paths(&self, content: &str) -> Vec<String> {
+        self.pattern
+            .captures_iter(content)
+            .filter_map(|c| c.name(PATH_GROUP_NAME).or_else(|| c.get(1)))
+            .map(|m| m.as_str().to_string())
+            .collect()
+    }
+
+    fn resolve(&self, manifest_dir: &RawTarget, path: String) -> Result<RawTarget, FailedParent> {
+        let path = BuildSystemPath::new(path, self.config.path_format);
+        let resolved = resolve_parent(self.repo.as_ref(), &path, manifest_dir)?;
+        self.repo
+            .enclosing_package(&resolved)
+            .ok_or_else(|| FailedParent {
+                name: path.raw.clone(),
+                reason: format!("path={} is not part of any package", resolved),
+                location: None,
+            })
+    }
+}
+
+impl Infer for ConfiguredInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![self.config.manifest.clone()])
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let base = self.repo.target_name_to_path(&t.name);
+        let manifests: Vec<_> = self
+            .repo
+            .package_files(t)
+            .into_iter()
+            .filter(|f| {
+                f.strip_prefix(&base)
+                    .is_ok_and(|rel| self.manifest.is_match(rel))
+            })
+            .collect();
+        if manifests.is_empty() {
+            return Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            });
+        }
+
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        for manifest in &manifests {
+            let Some(content) = self.repo.get_content(manifest)? else {
+                continue;
+            };
+            let dir = manifest.parent().unwrap_or(Path::new(""));
+            let dir_name = dir
+                .to_str()
+                .ok_or(anyhow!("could not parse path: {:?}", dir))?
+                .to_string();
+            let manifest_dir = RawTarget::from_string_name(dir_name)?;
+            let before = failed.len();
+            for path in self.get_paths(&content) {
+                match self.resolve(&manifest_dir, path) {
+                    Ok(p) => {
+                        if p != *t && !success.contains(&p) {
+                            success.push(p);
+                        }
+                    }
+                    Err(f) => failed.push(f),
+                }
+            }
+            locate_failed_parents(&mut failed[before..], manifest, &content);
+        }
+        info!(
+            "ConfiguredInfer({}): detected package={}",
+            self.config.flavor, t
+        );
+        Ok(InferResult {
+            inferred_target: InferredTarget::One(Single {
+                target: Target::from_raw_target(t, self.config.flavor.clone())
+                    .context(anyhow!("failed in creating target for package={}", t.name))?,
+                parents: success,
+                failed_parents: failed,
+            }),
+            what_next: Next::Continue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        config::InferrerConfig,
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, PathFormat, RawTarget},
+    };
+
+    use super::ConfiguredInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "dotnet/app";
+        let app_str = r#"
+<Project Sdk="Microsoft.NET.Sdk">
+  <ItemGroup>
+    <ProjectReference Include="../Core/Core.csproj" />
+    <ProjectReference Include="../../libs/logging/src/Logging.csproj" />
+    <ProjectReference Include="../../../outside/Outside.csproj" />
+    <PackageReference Include="Newtonsoft.Json" Version="13.0.1" />
+  </ItemGroup>
+</Project>
+"#;
+        let tests_str = r#"<ProjectReference Include="../../Core/Core.csproj" />"#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/nabs.json", us_name), "{}".to_string()),
+                (format!("{}/App.csproj", us_name), app_str.to_string()),
+                (
+                    format!("{}/tests/Tests.csproj", us_name),
+                    tests_str.to_string(),
+                ),
+                ("dotnet/Core/nabs.json".to_string(), "{}".to_string()),
+                ("dotnet/Core/Core.csproj".to_string(), "".to_string()),
+                ("libs/logging/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let config = InferrerConfig {
+            flavor: "dotnet".to_string(),
+            manifest: "**/*.csproj".to_string(),
+            pattern: r#"<ProjectReference\s+Include="(?P<path>[^"]+)""#.to_string(),
+            path_format: PathFormat::Posix,
+        };
+        let inf = ConfiguredInfer::new(Arc::new(repo), config).unwrap();
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            assert_eq!(single.target.flavor, "dotnet");
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[&"dotnet/Core".to_string(), &"libs/logging".to_string()],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(
+                &failed_parents,
+                &["../../../outside/Outside.csproj".to_string()],
+            );
+            let location = single.failed_parents[0].location.as_ref().unwrap();
+            assert_eq!(location.file, PathBuf::from("dotnet/app/App.csproj"));
+            assert_eq!((location.line, location.column), (Some(6), Some(32)));
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
@@ -1,13 +1,67 @@
+use std::path::Path;
+
 use anyhow::Result;
 
-use crate::types::{RawTarget, Target};
+use crate::diagnostics::Location;
+use crate::error::NabsError;
+use crate::graph::EdgeKind;
+use crate::types::{BuildSystemPath, NABS_FILE_NAME, RawTarget, Repository, Target};
 
 /// if the inferrer fails for some parent during parsing, they should return this for that particular parent
 /// Useful to keep this information for showing diagnostics in the end
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FailedParent {
     pub name: String,
     pub reason: String,
+    /// where the dependency is declared, see `locate_failed_parents`
+    pub location: Option<Location>,
+}
+
+/// points every failure without a location to the first place its name is mentioned in `file`
+/// inferrers call this for each manifest they read, after collecting the failures found in it
+pub fn locate_failed_parents(failed: &mut [FailedParent], file: &Path, content: &str) {
+    for f in failed.iter_mut().filter(|f| f.location.is_none()) {
+        f.location = Some(Location::find(file, content, &f.name));
+    }
+}
+
+/// resolves a relative path from a package's manifest to the parent package it points to
+/// absolute paths and paths outside the workspace are returned as failures
+pub fn resolve_parent(
+    repo: &dyn Repository,
+    path: &BuildSystemPath,
+    t: &RawTarget,
+) -> Result<RawTarget, FailedParent> {
+    if path.is_absolute() {
+        return Err(FailedParent {
+            name: path.raw.clone(),
+            reason: "absolute paths are not allowed".to_string(),
+            location: None,
+        });
+    }
+    repo.resolve_rel_path(path, t).map_err(|e| FailedParent {
+        name: path.raw.clone(),
+        reason: format!("{}", e),
+        location: None,
+    })
+}
+
+/// a `nabs.json` which could not be read, reported as a failure of its package instead of failing the package
+/// links read these for settings like `proto_sources`, a broken file only loses the edges declared in it
+pub fn failed_config(repo: &dyn Repository, t: &RawTarget, e: anyhow::Error) -> FailedParent {
+    let file = repo.target_name_to_path(&t.name).join(NABS_FILE_NAME);
+    let line = match NabsError::find(&e) {
+        Some(NabsError::ManifestParse { line, .. }) => *line,
+        _ => None,
+    };
+    FailedParent {
+        name: file.to_string_lossy().to_string(),
+        reason: format!("{:#}", e),
+        location: Some(Location {
+            line,
+            ..Location::file(&file)
+        }),
+    }
 }
 
 #[derive(Debug)]
@@ -36,7 +90,8 @@ pub struct InferResult {
     pub what_next: Next,
 }
 
-pub trait Infer {
+/// inferrers run on many packages in parallel, see `InferRunner::build_graph`
+pub trait Infer: Send + Sync {
     // given a target, an infer would return us the true inferred target
     // and a list of raw targets of parent dependencies
     // or no target
@@ -53,4 +108,30 @@ pub trait Infer {
     // we could abstract that away, but is that necessary?
 
     fn from_raw_target(&self, t: &RawTarget) -> Result<InferResult>;
+
+    /// globs (relative to the package directory) of every file parents are read from
+    /// `ReverseIndex` scans only these for paths to other packages, `None` if parents can come from anywhere
+    fn manifests(&self) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// a `LinkInfer` never detects targets on its own
+/// it adds extra parents to every target another inferrer detected for a package
+/// this is useful for dependencies which don't belong to any single build system, like code generated from a schema package
+pub trait LinkInfer: Send + Sync {
+    fn extra_parents(&self, t: &RawTarget) -> Result<(Vec<RawTarget>, Vec<FailedParent>)>;
+
+    /// the kind of edge added for every parent returned by `extra_parents`
+    fn edge_kind(&self) -> EdgeKind {
+        EdgeKind::Declared
+    }
+
+    /// same as `Infer::manifests`
+    fn manifests(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// drops anything the link keeps about the whole workspace (like an index of every package), called by `InferRunner::forget_all`
+    fn reset(&self) {}
 }
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use anyhow::{Context, anyhow};
+use log::info;
+
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+    resolve_parent,
+};
+
+pub const BUNDLER_FLAVOR: &str = "ruby_bundler";
+pub const GEMFILE_NAME: &str = "Gemfile";
+
+/// infers a ruby package using its `Gemfile`
+/// local gems are declared with a `path` option, both syntaxes are supported
+/// `gem "x", path: "../x"` and `gem 'x', :path => '../x'`
+/// the `Gemfile` is ruby code, a path which is not a literal string (like `File.join(...)` or `"#{root}/x"`) is reported as `FailedParent`
+pub struct GemfileInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl GemfileInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+}
+
+impl Infer for GemfileInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![GEMFILE_NAME.to_string()])
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let path = self.repo.target_name_to_path(&t.name).join(GEMFILE_NAME);
+        let content = self.repo.get_content(&path)?;
+
+        match content {
+            None => Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            }),
+            Some(content) => {
+                let mut success = Vec::new();
+                let (paths, mut failed) = get_gem_paths(&content);
+                for p in paths {
+                    let p = BuildSystemPath::new(p, PathFormat::Posix);
+                    match resolve_parent(self.repo.as_ref(), &p, t) {
+                        Ok(raw_target) => success.push(raw_target),
+                        Err(f) => failed.push(f),
+                    }
+                }
+                locate_failed_parents(&mut failed, &path, &content);
+                info!("GemfileInfer: detected package={}", t);
+                Ok(InferResult {
+                    inferred_target: InferredTarget::One(Single {
+                        target: Target::from_raw_target(t, BUNDLER_FLAVOR.to_string())
+                            .context(anyhow!("failed in creating target for package={}", t.name))?,
+                        parents: success,
+                        failed_parents: failed,
+                    }),
+                    what_next: Next::Continue,
+                })
+            }
+        }
+    }
+}
+
+/// literal `path` options of every `gem`, along with the ones which can't be read statically
+fn get_gem_paths(content: &str) -> (Vec<String>, Vec<FailedParent>) {
+    let mut paths = Vec::new();
+    let mut failed = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if !(line.starts_with("gem ") || line.starts_with("gem(")) {
+            continue;
+        }
+        let value = line
+            .find("path:")
+            .map(|i| &line[i + "path:".len()..])
+            .or_else(|| {
+                let i = line.find(":path")?;
+                line[i + ":path".len()..].trim_start().strip_prefix("=>")
+            });
+        let Some(value) = value else {
+            continue;
+        };
+        match quoted(value) {
+            Some(path) if !path.contains("#{") => paths.push(path.to_string()),
+            _ => failed.push(FailedParent {
+                name: value.trim().to_string(),
+                reason: "only string literals without interpolation are supported".to_string(),
+                location: None,
+            }),
+        }
+    }
+    (paths, failed)
+}
+
+/// the content of a string literal at the start of `s`, ignoring leading whitespace
+fn quoted(s: &str) -> Option<&str> {
+    let s = s.trim_start();
+    let quote = s.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let rest = &s[1..];
+    rest.find(quote).map(|end| &rest[..end])
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::GemfileInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "web/storefront";
+        let gemfile_str = r##"
+            source "https://rubygems.org"
+
+            gem "rails", "~> 7.1"
+            gem "billing", path: "../billing"
+            gem 'auth', :path => '../../libs/auth'
+            gem "search", path: File.join(__dir__, "search")
+            gem "escape", path: "../../../escape"
+            gem "root", path: "/opt/gems/root"
+            gem "shared", path: "#{ENV['ROOT']}/shared"
+            # gem "commented", path: "../commented"
+        "##;
+        let repo = MockRepo::new(
+            HashMap::from([(format!("{}/Gemfile", us_name), gemfile_str.to_string())]),
+            PathBuf::new(),
+        );
+        let inf = GemfileInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[&"web/billing".to_string(), &"libs/auth".to_string()],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(
+                &failed_parents,
+                &[
+                    "File.join(__dir__, \"search\")".to_string(),
+                    "../../../escape".to_string(),
+                    "/opt/gems/root".to_string(),
+                    "\"#{ENV['ROOT']}/shared\"".to_string(),
+                ],
+            );
+            assert!(single.failed_parents.iter().all(|p| p.location.is_some()));
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
@@ -1,10 +1,21 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::{Context, Result, anyhow};
+use regex::Regex;
 use serde::Deserialize;
 
-use super::core::{FailedParent, Infer, InferResult, InferredTarget, Next, Single};
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+};
+use crate::diagnostics::Location;
+use crate::error::NabsError;
 use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+use crate::warning::WarningKind;
 
 pub const CARGO_FLAVOR: &str = "cargo";
 
@@ -22,8 +33,18 @@ enum Dependency {
     Simple(String),
 }
 
+#[derive(Debug, Deserialize)]
+struct NameSection {
+    #[serde(default)]
+    name: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct CargoToml {
+    #[serde(default)]
+    package: Option<NameSection>,
+    #[serde(default)]
+    lib: Option<NameSection>,
     #[serde(default)]
     dependencies: HashMap<String, Dependency>,
     #[serde(default)]
@@ -32,19 +53,327 @@ struct CargoToml {
 }
 
 pub struct CargoInfer {
-    repo: Rc<dyn Repository>,
+    repo: Arc<dyn Repository>,
 }
 
 impl CargoInfer {
-    pub fn new(repo: Rc<dyn Repository>) -> Self {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
         Self { repo }
     }
+
+    fn get_cargo_toml(&self, t: &RawTarget) -> Result<Option<CargoToml>> {
+        Ok(self
+            .read_cargo_toml(t)?
+            .map(|(_, _, cargo_toml)| cargo_toml))
+    }
+
+    /// the path and content of `Cargo.toml` along with the parsed manifest
+    fn read_cargo_toml(&self, t: &RawTarget) -> Result<Option<(PathBuf, String, CargoToml)>> {
+        let path = self.repo.target_name_to_path(&t.name).join("Cargo.toml");
+        match self.repo.get_content(&path)? {
+            None => Ok(None),
+            Some(content) => {
+                let cargo_toml = toml::from_str(&content)
+                    .map_err(|e| NabsError::from_toml(path.clone(), &content, &e))?;
+                Ok(Some((path, content, cargo_toml)))
+            }
+        }
+    }
+
+    /// the name other crates use to refer to this package in code (`[lib] name`, or the package name with `-` replaced by `_`)
+    /// `None` if this is not a cargo package
+    pub fn crate_name(&self, t: &RawTarget) -> Result<Option<String>> {
+        let Some(cargo_toml) = self.get_cargo_toml(t)? else {
+            return Ok(None);
+        };
+        let name = cargo_toml
+            .lib
+            .and_then(|l| l.name)
+            .or(cargo_toml.package.and_then(|p| p.name));
+        Ok(name.map(|n| to_crate_ident(&n)))
+    }
+
+    /// cross checks the path dependencies in `Cargo.toml` with the crates actually used in the `.rs` files of the package
+    /// `crates` maps the crate name of every cargo package in the workspace to its package
+    /// every problem points to the dependency in `Cargo.toml`, or to the file if it is not declared there
+    /// `None` if this is not a cargo package
+    pub fn check_usage(
+        &self,
+        t: &RawTarget,
+        crates: &HashMap<String, RawTarget>,
+    ) -> Result<Option<Vec<(CargoUsageProblem, Location)>>> {
+        let Some((toml_path, content, cargo_toml)) = self.read_cargo_toml(t)? else {
+            return Ok(None);
+        };
+        let mut used = HashSet::new();
+        for f in self.repo.package_files(t) {
+            if f.extension().is_none_or(|e| e != "rs") {
+                continue;
+            }
+            if let Some(content) = self.repo.get_content(&f)? {
+                used.extend(get_path_roots(&content));
+            }
+        }
+
+        let mut problems = Vec::new();
+        // crate names in code, for every dependency with a path which resolves to a workspace package
+        let mut declared_packages = HashSet::new();
+        // crate names in code, to the key they are declared with in `Cargo.toml`
+        let mut declared_names = HashMap::new();
+        let deps = cargo_toml
+            .dependencies
+            .into_iter()
+            .chain(cargo_toml.dev_dependencies);
+        for (name, dep) in deps {
+            let ident = to_crate_ident(&name);
+            declared_names.insert(ident.clone(), name.clone());
+            let Dependency::Object(FullDep { path: Some(path) }) = dep else {
+                continue;
+            };
+            let Ok(parent) = self
+                .repo
+                .resolve_rel_path(&BuildSystemPath::new(path.clone(), PathFormat::Posix), t)
+            else {
+                // broken paths are already reported by inference
+                continue;
+            };
+            // a dependency can be used by its `[lib] name` instead of the key in `Cargo.toml`
+            let lib_ident = crates
+                .iter()
+                .find(|(_, p)| **p == parent)
+                .map(|(n, _)| n.clone());
+            if !used.contains(&ident) && lib_ident.is_none_or(|l| !used.contains(&l)) {
+                let location = dependency_location(&toml_path, &content, &name);
+                problems.push((
+                    CargoUsageProblem::UnusedPathDependency { name, path },
+                    location,
+                ));
+            }
+            declared_packages.insert(parent);
+        }
+
+        let mut used: Vec<String> = used.into_iter().collect();
+        used.sort();
+        for crate_name in used {
+            let Some(package) = crates.get(&crate_name) else {
+                continue;
+            };
+            if package == t || declared_packages.contains(package) {
+                continue;
+            }
+            let location = match declared_names.get(&crate_name) {
+                Some(name) => dependency_location(&toml_path, &content, name),
+                None => Location::file(&toml_path),
+            };
+            problems.push((
+                CargoUsageProblem::UndeclaredWorkspaceCrate {
+                    declared: declared_names.contains_key(&crate_name),
+                    crate_name,
+                    package: package.clone(),
+                },
+                location,
+            ));
+        }
+        Ok(Some(problems))
+    }
+}
+
+/// a mismatch between `Cargo.toml` and the code of a cargo package
+#[derive(Debug)]
+pub enum CargoUsageProblem {
+    /// a path dependency which is never used in any `.rs` file
+    /// it makes changesets bigger than they need to be
+    UnusedPathDependency { name: String, path: String },
+    /// a workspace crate used in code, which nabs has no edge for
+    /// `declared` is true if `Cargo.toml` has the dependency, but without a `path` (like `workspace = true`)
+    UndeclaredWorkspaceCrate {
+        crate_name: String,
+        package: RawTarget,
+        declared: bool,
+    },
+}
+
+impl CargoUsageProblem {
+    /// problems are reported as warnings of this kind, its code is useful for grepping or allowlisting
+    pub fn kind(&self) -> WarningKind {
+        match self {
+            CargoUsageProblem::UnusedPathDependency { .. } => {
+                WarningKind::CargoUnusedPathDependency
+            }
+            CargoUsageProblem::UndeclaredWorkspaceCrate { .. } => {
+                WarningKind::CargoUndeclaredWorkspaceCrate
+            }
+        }
+    }
+
+    /// the dependency or crate the problem is about
+    pub fn subject(&self) -> &str {
+        match self {
+            CargoUsageProblem::UnusedPathDependency { name, .. } => name,
+            CargoUsageProblem::UndeclaredWorkspaceCrate { crate_name, .. } => crate_name,
+        }
+    }
+}
+
+impl Display for CargoUsageProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CargoUsageProblem::UnusedPathDependency { name, path } => write!(
+                f,
+                "path dependency {} (path={}) is declared in Cargo.toml but never used",
+                name, path
+            ),
+            CargoUsageProblem::UndeclaredWorkspaceCrate {
+                crate_name,
+                package,
+                declared: true,
+            } => write!(
+                f,
+                "crate {} (package={}) is used, but declared without a `path` in Cargo.toml, nabs can't track this dependency",
+                crate_name, package
+            ),
+            CargoUsageProblem::UndeclaredWorkspaceCrate {
+                crate_name,
+                package,
+                declared: false,
+            } => write!(
+                f,
+                "crate {} (package={}) is used, but not declared in Cargo.toml",
+                crate_name, package
+            ),
+        }
+    }
+}
+
+/// where dependency `name` is declared in `Cargo.toml`, like `name = { path = ".." }` or `[dependencies.name]`
+/// only the file if it can't be found
+fn dependency_location(file: &Path, content: &str, name: &str) -> Location {
+    let pattern = format!(
+        r#"(?m)^\s*(?:\[(?:dev-)?dependencies\.)?"?({})"?\s*[=\]]"#,
+        regex::escape(name)
+    );
+    let found = Regex::new(&pattern)
+        .ok()
+        .and_then(|re| re.captures(content))
+        .and_then(|c| c.get(1));
+    match found {
+        Some(m) => Location::at(file, content, m.start()),
+        None => Location::file(file),
+    }
+}
+
+fn to_crate_ident(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// the first segment of every path in rust code, like `foo` in `foo::bar()`, `use foo::{a, b}` and `extern crate foo`
+/// comments and string literals are skipped
+fn get_path_roots(content: &str) -> HashSet<String> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut roots = HashSet::new();
+    let mut prev_ident: Option<String> = None;
+    let mut prev_was_path_sep = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            i += 2;
+            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+        if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i += 1;
+            prev_ident = None;
+            prev_was_path_sep = false;
+            continue;
+        }
+        if c == '\'' {
+            // char literals, `'"'` would otherwise start a string. lifetimes (`'a`) are left alone
+            if next == Some('\\') {
+                i += 2;
+                while i < chars.len() && chars[i] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            if chars.get(i + 2) == Some(&'\'') {
+                i += 3;
+                continue;
+            }
+        }
+        if c == 'r' && (next == Some('"') || next == Some('#')) {
+            // raw strings, `r#"..."#`
+            let mut j = i + 1;
+            while chars.get(j) == Some(&'#') {
+                j += 1;
+            }
+            if chars.get(j) == Some(&'"') {
+                let hashes = j - i - 1;
+                i = j + 1;
+                while i < chars.len() {
+                    if chars[i] == '"' && chars[i + 1..].iter().take(hashes).all(|c| *c == '#') {
+                        break;
+                    }
+                    i += 1;
+                }
+                i += 1 + hashes;
+                prev_ident = None;
+                prev_was_path_sep = false;
+                continue;
+            }
+        }
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+            let is_path_start = chars.get(i) == Some(&':') && chars.get(i + 1) == Some(&':');
+            let after_keyword =
+                matches!(prev_ident.as_deref(), Some("crate") | Some("use")) && !prev_was_path_sep;
+            if (is_path_start && !prev_was_path_sep) || after_keyword {
+                roots.insert(ident.clone());
+            }
+            prev_ident = Some(ident);
+            prev_was_path_sep = false;
+            continue;
+        }
+        if c == ':' && next == Some(':') {
+            prev_was_path_sep = true;
+            i += 2;
+            continue;
+        }
+        if !c.is_whitespace() {
+            prev_ident = None;
+            prev_was_path_sep = false;
+        }
+        i += 1;
+    }
+    roots
 }
 
 fn get_parents(
     our_target: &RawTarget,
     cargo_toml: CargoToml,
-    repo: &Rc<dyn Repository>,
+    repo: &Arc<dyn Repository>,
 ) -> Result<(Vec<RawTarget>, Vec<FailedParent>)> {
     let (mut s, mut f) = deps_to_raw_targets(our_target, cargo_toml.dependencies, repo)?;
     let (s1, f1) = deps_to_raw_targets(our_target, cargo_toml.dev_dependencies, repo)?;
@@ -56,7 +385,7 @@ fn get_parents(
 fn deps_to_raw_targets(
     our_target: &RawTarget,
     deps: HashMap<String, Dependency>,
-    repo: &Rc<dyn Repository>,
+    repo: &Arc<dyn Repository>,
 ) -> Result<(Vec<RawTarget>, Vec<FailedParent>)> {
     let mut success = Vec::new();
     let mut failed = Vec::new();
@@ -71,6 +400,7 @@ fn deps_to_raw_targets(
                 failed.push(FailedParent {
                     name: path.raw.clone(),
                     reason: "absolute paths are not allowed".to_string(),
+                    location: None,
                 });
             } else {
                 match repo.resolve_rel_path(&path, our_target) {
@@ -78,6 +408,7 @@ fn deps_to_raw_targets(
                     Err(e) => failed.push(FailedParent {
                         name: path.raw.clone(),
                         reason: format!("{}", e),
+                        location: None,
                     }),
                 };
             }
@@ -87,19 +418,19 @@ fn deps_to_raw_targets(
 }
 
 impl Infer for CargoInfer {
-    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<super::core::InferResult> {
-        let content = self
-            .repo
-            .get_content(&self.repo.target_name_to_path(&t.name).join("Cargo.toml"));
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec!["Cargo.toml".to_string()])
+    }
 
-        match content {
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<super::core::InferResult> {
+        match self.read_cargo_toml(t)? {
             None => Ok(InferResult {
                 inferred_target: InferredTarget::Nothing,
                 what_next: Next::Continue,
             }),
-            Some(content) => {
-                let cargo_toml: CargoToml = toml::from_str(&content)?;
-                let (success, failed) = get_parents(t, cargo_toml, &self.repo)?;
+            Some((path, content, cargo_toml)) => {
+                let (success, mut failed) = get_parents(t, cargo_toml, &self.repo)?;
+                locate_failed_parents(&mut failed, &path, &content);
                 let target = Target::from_raw_target(&t, CARGO_FLAVOR.to_string()).context(
                     anyhow!("failed in creating target in cargo inferrer, package={}", t),
                 )?;
@@ -119,14 +450,103 @@ impl Infer for CargoInfer {
 
 #[cfg(test)]
 mod test {
-    use std::{collections::HashMap, path::PathBuf, rc::Rc};
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
     use crate::{
         infer::core::{Infer, InferredTarget, Next},
         types::{MockRepo, RawTarget},
     };
 
-    use super::CargoInfer;
+    use super::{CargoInfer, CargoUsageProblem};
+
+    #[test]
+    fn test_check_usage() {
+        let us_name = "crates/app";
+        let toml_str = r#"
+            [package]
+            name = "app"
+
+            [dependencies]
+            used-lib = { path = "../used_lib" }
+            renamed = { path = "../renamed_lib" }
+            stale = { path = "../stale" }
+            inherited = { workspace = true }
+
+            [dev-dependencies]
+            test-utils = { path = "../test_utils" }
+        "#;
+        let main_rs = r##"
+            extern crate renamed_core;
+            use used_lib::Client;
+            use crate::local::thing;
+            // stale::commented_out();
+            fn main() {
+                let s = "stale::in_a_string";
+                let c = '"';
+                inherited::run();
+                undeclared::go(r#"stale::raw"#);
+            }
+        "##;
+        let test_rs = "use test_utils::fixture;";
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/Cargo.toml", us_name), toml_str.to_string()),
+                (format!("{}/src/main.rs", us_name), main_rs.to_string()),
+                (format!("{}/tests/it.rs", us_name), test_rs.to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let crates = HashMap::from([
+            ("app", "crates/app"),
+            ("used_lib", "crates/used_lib"),
+            ("renamed_core", "crates/renamed_lib"),
+            ("stale", "crates/stale"),
+            ("test_utils", "crates/test_utils"),
+            ("inherited", "crates/inherited"),
+            ("undeclared", "crates/undeclared"),
+        ])
+        .into_iter()
+        .map(|(k, v)| {
+            (
+                k.to_string(),
+                RawTarget::from_string_name(v.to_string()).unwrap(),
+            )
+        })
+        .collect();
+
+        let inf = CargoInfer::new(Arc::new(repo));
+        let problems = inf
+            .check_usage(
+                &RawTarget::from_string_name(us_name.to_string()).unwrap(),
+                &crates,
+            )
+            .unwrap()
+            .unwrap();
+        let got: Vec<String> = problems
+            .iter()
+            .map(|(p, location)| {
+                let problem = match p {
+                    CargoUsageProblem::UnusedPathDependency { name, .. } => {
+                        format!("unused:{}", name)
+                    }
+                    CargoUsageProblem::UndeclaredWorkspaceCrate {
+                        crate_name,
+                        declared,
+                        ..
+                    } => format!("undeclared:{}:{}", crate_name, declared),
+                };
+                format!("{} at {}", problem, location)
+            })
+            .collect();
+        compare_vec(
+            &got,
+            &vec![
+                "unused:stale at crates/app/Cargo.toml:8:13".to_string(),
+                "undeclared:inherited:true at crates/app/Cargo.toml:9:13".to_string(),
+                "undeclared:undeclared:false at crates/app/Cargo.toml".to_string(),
+            ],
+        );
+    }
 
     #[test]
     fn test_infer() {
@@ -152,7 +572,7 @@ mod test {
             PathBuf::new(),
         );
         let inf = CargoInfer {
-            repo: Rc::new(repo),
+            repo: Arc::new(repo),
         };
         let infer_result = inf
             .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use anyhow::{Context, anyhow};
+use log::info;
+
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+    resolve_parent,
+};
+
+pub const NIX_FLAVOR: &str = "nix";
+pub const NIX_FILE_NAMES: [&str; 2] = ["flake.nix", "default.nix"];
+const PATH_URL_PREFIX: &str = "path:";
+
+/// infers a nix package using its `flake.nix` and `default.nix`
+/// two kinds of references are used
+/// - flake inputs using the `path:` scheme, like `inputs.lib.url = "path:../lib";`
+/// - relative path literals, like `import ../lib/default.nix` or `src = ../common;`
+///   nix copies anything referenced by a path literal into the store, so every such literal is a dependency
+///
+/// a path can point anywhere inside a package, the dependency is the package enclosing it
+/// interpolated paths (`./${name}.nix`) and lookup paths (`<nixpkgs>`) can't be resolved statically
+/// interpolated paths are reported as `FailedParent`, lookup paths are outside the workspace and ignored
+pub struct NixInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl NixInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+
+    fn resolve(&self, t: &RawTarget, path: String) -> Result<RawTarget, FailedParent> {
+        if path.contains("${") {
+            return Err(FailedParent {
+                name: path,
+                reason: "interpolated paths are not supported".to_string(),
+                location: None,
+            });
+        }
+        let path = BuildSystemPath::new(path, PathFormat::Posix);
+        let resolved = resolve_parent(self.repo.as_ref(), &path, t)?;
+        self.repo
+            .enclosing_package(&resolved)
+            .ok_or_else(|| FailedParent {
+                name: path.raw.clone(),
+                reason: format!("path={} is not part of any package", resolved),
+                location: None,
+            })
+    }
+}
+
+impl Infer for NixInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(NIX_FILE_NAMES.iter().map(|n| n.to_string()).collect())
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let base = self.repo.target_name_to_path(&t.name);
+        let mut contents = Vec::new();
+        for name in NIX_FILE_NAMES {
+            let path = base.join(name);
+            if let Some(content) = self.repo.get_content(&path)? {
+                contents.push((path, content));
+            }
+        }
+        if contents.is_empty() {
+            return Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            });
+        }
+
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        for (file, content) in &contents {
+            let before = failed.len();
+            for path in get_paths(content) {
+                match self.resolve(t, path) {
+                    Ok(p) => {
+                        if p != *t && !success.contains(&p) {
+                            success.push(p);
+                        }
+                    }
+                    Err(f) => failed.push(f),
+                }
+            }
+            locate_failed_parents(&mut failed[before..], file, content);
+        }
+        info!("NixInfer: detected package={}", t);
+        Ok(InferResult {
+            inferred_target: InferredTarget::One(Single {
+                target: Target::from_raw_target(t, NIX_FLAVOR.to_string())
+                    .context(anyhow!("failed in creating target for package={}", t.name))?,
+                parents: success,
+                failed_parents: failed,
+            }),
+            what_next: Next::Continue,
+        })
+    }
+}
+
+/// relative path literals and `path:` urls in a nix file
+fn get_paths(content: &str) -> Vec<String> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut paths = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let prev = if i > 0 { chars[i - 1] } else { ' ' };
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' {
+            let (s, end) = read_string(&chars, i + 1);
+            if let Some(url) = s.strip_prefix(PATH_URL_PREFIX) {
+                // `path:../lib?narHash=...`
+                let url = url.split_once('?').map_or(url, |(p, _)| p);
+                if !url.starts_with('/') {
+                    paths.push(url.to_string());
+                }
+            }
+            i = end;
+        } else if c == '\'' && chars.get(i + 1) == Some(&'\'') {
+            // indented strings, these never contain paths we care about
+            i += 2;
+            while i + 1 < chars.len() && !(chars[i] == '\'' && chars[i + 1] == '\'') {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '.' && !is_ident_char(prev) && starts_path(&chars[i..]) {
+            let start = i;
+            while i < chars.len() {
+                if chars[i] == '$' && chars.get(i + 1) == Some(&'{') {
+                    while i < chars.len() && chars[i] != '}' {
+                        i += 1;
+                    }
+                } else if !is_path_char(chars[i]) {
+                    break;
+                }
+                i += 1;
+            }
+            paths.push(chars[start..i].iter().collect());
+        } else {
+            i += 1;
+        }
+    }
+    paths
+}
+
+/// reads a double quoted string starting right after the opening quote
+/// returns the content and the index right after the closing quote
+fn read_string(chars: &[char], mut i: usize) -> (String, usize) {
+    let mut s = String::new();
+    while i < chars.len() && chars[i] != '"' {
+        if chars[i] == '\\' && i + 1 < chars.len() {
+            i += 1;
+        }
+        s.push(chars[i]);
+        i += 1;
+    }
+    (s, i + 1)
+}
+
+fn starts_path(chars: &[char]) -> bool {
+    let s: String = chars.iter().take(3).collect();
+    s.starts_with("./") || s.starts_with("../")
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '\'' || c == '.'
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '+' | '/')
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::NixInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "tools/devshell";
+        let flake_str = r#"
+            {
+              inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-24.05";
+              inputs.lib.url = "path:../../nix/lib";
+              inputs.fmt = { url = "path:../fmt?dir=x"; };
+              # inputs.old.url = "path:../old";
+              outputs = { self, nixpkgs, lib, fmt }: {
+                devShells.default = import ./shell.nix { inherit nixpkgs; };
+              };
+            }
+        "#;
+        let default_str = r#"
+            { pkgs ? import <nixpkgs> {} }:
+            let
+              common = import ../../nix/lib/common/default.nix;
+              overlay = pkgs.callPackage ../../nix/overlays { };
+              generated = import ./${name}.nix;
+              escaped = import ../../../../outside.nix;
+              script = ''
+                cat ../not/a/path
+              '';
+            in pkgs.mkShell { src = ./.; version = 1.0; }
+        "#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/flake.nix", us_name), flake_str.to_string()),
+                (format!("{}/default.nix", us_name), default_str.to_string()),
+                (format!("{}/nabs.json", us_name), "{}".to_string()),
+                ("nix/lib/nabs.json".to_string(), "{}".to_string()),
+                ("nix/overlays/nabs.json".to_string(), "{}".to_string()),
+                ("tools/fmt/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let inf = NixInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[
+                    &"nix/lib".to_string(),
+                    &"nix/overlays".to_string(),
+                    &"tools/fmt".to_string(),
+                ],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(
+                &failed_parents,
+                &[
+                    "./${name}.nix".to_string(),
+                    "../../../../outside.nix".to_string(),
+                ],
+            );
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
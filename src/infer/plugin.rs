@@ -0,0 +1,257 @@
+/// inferrers implemented outside nabs, as executables declared in the `plugins` list of `workspace.json`
+///
+/// for every package, nabs runs `<command> <args...> <absolute package directory>` from the workspace root
+/// the request is written to the plugin's stdin
+/// ```json
+/// { "package": "libs/foo", "workspace_root": "/home/me/monorepo" }
+/// ```
+/// the plugin replies on stdout, every field except `target` can be skipped
+/// ```json
+/// {
+///     "target": { "flavor": "make" },
+///     "parents": ["../bar"],
+///     "failed_parents": [{ "name": "$(ROOT)/baz", "reason": "variables are not supported" }],
+///     "what_next": "continue"
+/// }
+/// ```
+/// `target` is `null` if the plugin does not recognise the package
+/// `parents` are posix paths relative to the package directory, like the paths in any other manifest
+/// a non zero exit code fails inference for the package, stderr is added to the error
+use std::{
+    io::{ErrorKind, Write},
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::config::PluginConfig;
+use crate::diagnostics::Location;
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+
+use super::core::{FailedParent, Infer, InferResult, InferredTarget, Next, Single, resolve_parent};
+
+#[derive(Debug, Serialize)]
+pub(super) struct PluginRequest<'a> {
+    pub package: &'a str,
+    pub workspace_root: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginTarget {
+    flavor: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginFailedParent {
+    name: String,
+    reason: String,
+    /// relative to the workspace root
+    #[serde(default)]
+    location: Option<Location>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PluginNext {
+    #[default]
+    Continue,
+    Break,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct PluginResponse {
+    target: Option<PluginTarget>,
+    #[serde(default)]
+    parents: Vec<String>,
+    #[serde(default)]
+    failed_parents: Vec<PluginFailedParent>,
+    #[serde(default)]
+    what_next: PluginNext,
+}
+
+pub struct PluginInfer {
+    repo: Arc<dyn Repository>,
+    config: PluginConfig,
+}
+
+impl PluginInfer {
+    pub fn new(repo: Arc<dyn Repository>, config: PluginConfig) -> Self {
+        Self { repo, config }
+    }
+
+    fn call(&self, t: &RawTarget) -> Result<PluginResponse> {
+        let root = self.repo.workspace_root();
+        let program = if self.config.command.contains('/') {
+            root.join(&self.config.command)
+        } else {
+            self.config.command.clone().into()
+        };
+        let request = serde_json::to_string(&PluginRequest {
+            package: t.name.to_string_ref(),
+            workspace_root: &root.to_string_lossy(),
+        })?;
+
+        let mut child = Command::new(&program)
+            .args(&self.config.args)
+            .arg(root.join(self.repo.target_name_to_path(&t.name)))
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(anyhow!("failed in running plugin={}", self.config.command))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // a plugin is free to exit without reading the request
+            match stdin.write_all(request.as_bytes()) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "plugin={} failed for package={}, status={}, stderr:\n{}",
+                self.config.command,
+                t,
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        serde_json::from_slice(&output.stdout).context(anyhow!(
+            "failed in parsing response of plugin={} for package={}",
+            self.config.command,
+            t
+        ))
+    }
+}
+
+impl Infer for PluginInfer {
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let response = self.call(t)?;
+        into_infer_result(self.repo.as_ref(), t, response, &self.config.command)
+    }
+}
+
+/// converts a plugin's response to our `InferResult`, `plugin` is only used for logging
+pub(super) fn into_infer_result(
+    repo: &dyn Repository,
+    t: &RawTarget,
+    response: PluginResponse,
+    plugin: &str,
+) -> Result<InferResult> {
+    let what_next = match response.what_next {
+        PluginNext::Continue => Next::Continue,
+        PluginNext::Break => Next::Break,
+    };
+    let Some(target) = response.target else {
+        return Ok(InferResult {
+            inferred_target: InferredTarget::Nothing,
+            what_next,
+        });
+    };
+
+    let mut success = Vec::new();
+    let mut failed: Vec<FailedParent> = response
+        .failed_parents
+        .into_iter()
+        .map(|f| FailedParent {
+            name: f.name,
+            reason: f.reason,
+            location: f.location,
+        })
+        .collect();
+    for path in response.parents {
+        let p = BuildSystemPath::new(path, PathFormat::Posix);
+        match resolve_parent(repo, &p, t) {
+            Ok(raw_target) => success.push(raw_target),
+            Err(f) => failed.push(f),
+        }
+    }
+    info!("Plugin({}): detected package={}", plugin, t);
+    Ok(InferResult {
+        inferred_target: InferredTarget::One(Single {
+            target: Target::from_raw_target(t, target.flavor)
+                .context(anyhow!("failed in creating target for package={}", t.name))?,
+            parents: success,
+            failed_parents: failed,
+        }),
+        what_next,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use crate::{
+        config::PluginConfig,
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::PluginInfer;
+
+    fn fake_plugin(script: &str) -> PluginInfer {
+        let repo = MockRepo::new(HashMap::new(), std::env::temp_dir());
+        PluginInfer::new(
+            Arc::new(repo),
+            PluginConfig {
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string(), "plugin".to_string()],
+            },
+        )
+    }
+
+    #[test]
+    fn test_infer() {
+        let us_name = "tools/make/app";
+        // echoes the package name from the request back as a failed parent
+        let inf = fake_plugin(
+            r#"
+            pkg=$(sed 's/.*"package":"\([^"]*\)".*/\1/')
+            case "$1" in */tools/make/app) ;; *) exit 1 ;; esac
+            echo '{"target": {"flavor": "make"}, "parents": ["../lib", "../../../../outside"],'
+            echo "\"failed_parents\": [{\"name\": \"$pkg\", \"reason\": \"test\"}]}"
+            "#,
+        );
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            assert_eq!(single.target.flavor, "make");
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            assert_eq!(parents, vec!["tools/make/lib"]);
+            let failed_parents: Vec<&String> =
+                single.failed_parents.iter().map(|p| &p.name).collect();
+            assert_eq!(failed_parents, vec![us_name, "../../../../outside"]);
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+
+        let inf = fake_plugin(r#"echo '{"target": null, "what_next": "break"}'"#);
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+        assert_eq!(infer_result.what_next, Next::Break);
+        assert!(matches!(
+            infer_result.inferred_target,
+            InferredTarget::Nothing
+        ));
+
+        let inf = fake_plugin("echo broken >&2; exit 3");
+        let err = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap_err();
+        assert!(format!("{}", err).contains("broken"));
+    }
+}
@@ -0,0 +1,416 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use log::info;
+
+use crate::paths::normalize_path;
+use crate::types::{
+    BuildSystemPath, NABS_FILE_NAME, PathFormat, RawTarget, Repository, Target, TargetName,
+};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, LinkInfer, Next, Single, failed_config,
+    locate_failed_parents,
+};
+
+pub const PROTO_FLAVOR: &str = "proto";
+const PROTO_EXTENSION: &str = "proto";
+/// imports shipped with protoc itself, these never live in the workspace
+const WELL_KNOWN_IMPORT_PREFIX: &str = "google/protobuf/";
+
+/// infers a proto package, any package containing `.proto` files inside one of the configured include roots
+/// `.proto` files outside the roots can't be imported by anyone, packages only containing those are left to other inferrers
+/// every `import "common/types.proto";` is resolved against the include roots (like `protoc -I`)
+/// the first root where the file exists wins, the dependency is the package enclosing that file
+/// imports which can't be found in any root are reported as `FailedParent`
+pub struct ProtoInfer {
+    repo: Arc<dyn Repository>,
+    include_roots: Vec<String>,
+}
+
+impl ProtoInfer {
+    pub fn new(repo: Arc<dyn Repository>, include_roots: Vec<String>) -> Self {
+        Self {
+            repo,
+            include_roots,
+        }
+    }
+
+    /// the include roots without `.` and `..`, a root like `.` (the usual `protoc -I .`) is the workspace root
+    fn roots(&self) -> impl Iterator<Item = String> {
+        self.include_roots.iter().filter_map(|r| normalize_posix(r))
+    }
+
+    fn resolve_import(&self, import: &str) -> Result<RawTarget, FailedParent> {
+        let failed = |reason: String| FailedParent {
+            name: import.to_string(),
+            reason,
+            location: None,
+        };
+        for root in &self.include_roots {
+            // a candidate outside the workspace, or a file directly in the workspace root, can't be part of a package
+            let Some(file) = normalize_posix(&format!("{}/{}", root, import)) else {
+                continue;
+            };
+            let Some((dir, file_name)) = file.rsplit_once('/') else {
+                continue;
+            };
+            let Ok(dir) = TargetName::new(dir.to_string()) else {
+                continue;
+            };
+            let path = self.repo.target_name_to_path(&dir).join(file_name);
+            // an unreadable file still exists, the error shows up when it is scanned
+            if matches!(self.repo.get_content(&path), Ok(None)) {
+                continue;
+            }
+            return self
+                .repo
+                .enclosing_package(&RawTarget::new(dir))
+                .ok_or_else(|| failed(format!("file={} is not part of any package", file)));
+        }
+        Err(failed(format!(
+            "import not found in any include root, include_roots={:?}",
+            self.include_roots
+        )))
+    }
+}
+
+/// a posix path without `.` and `..`, `None` if it points outside the workspace
+fn normalize_posix(path: &str) -> Option<String> {
+    let normalized = normalize_path(Path::new(path)).ok()?;
+    let parts: Option<Vec<&str>> = normalized
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect();
+    Some(parts?.join("/"))
+}
+
+impl Infer for ProtoInfer {
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let proto_files: Vec<_> = self
+            .repo
+            .package_files(t)
+            .into_iter()
+            .filter(|f| f.extension().is_some_and(|e| e == PROTO_EXTENSION))
+            .filter(|f| self.roots().any(|r| f.starts_with(&r)))
+            .collect();
+        if proto_files.is_empty() {
+            return Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            });
+        }
+
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        for f in &proto_files {
+            let Some(content) = self.repo.get_content(f)? else {
+                continue;
+            };
+            let before = failed.len();
+            for import in get_imports(&content) {
+                if import.starts_with(WELL_KNOWN_IMPORT_PREFIX) {
+                    continue;
+                }
+                match self.resolve_import(&import) {
+                    Ok(p) => {
+                        if p != *t && !success.contains(&p) {
+                            success.push(p);
+                        }
+                    }
+                    Err(e) => failed.push(e),
+                }
+            }
+            locate_failed_parents(&mut failed[before..], f, &content);
+        }
+        info!("ProtoInfer: detected package={}", t);
+        Ok(InferResult {
+            inferred_target: InferredTarget::One(Single {
+                target: Target::from_raw_target(t, PROTO_FLAVOR.to_string())
+                    .context(anyhow!("failed in creating target for package={}", t.name))?,
+                parents: success,
+                failed_parents: failed,
+            }),
+            what_next: Next::Continue,
+        })
+    }
+}
+
+/// links language packages to the proto packages they generate code from
+/// the proto packages are declared in the package's `nabs.json`, a `nabs.json` which can't be parsed is a `FailedParent`
+/// ```json
+/// { "proto_sources": ["../../proto/common"] }
+/// ```
+pub struct ProtoCodegenLink {
+    repo: Arc<dyn Repository>,
+}
+
+impl ProtoCodegenLink {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+}
+
+impl LinkInfer for ProtoCodegenLink {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![NABS_FILE_NAME.to_string()])
+    }
+
+    fn extra_parents(&self, t: &RawTarget) -> Result<(Vec<RawTarget>, Vec<FailedParent>)> {
+        let config = match self.repo.package_config(t) {
+            Ok(config) => config,
+            Err(e) => return Ok((Vec::new(), vec![failed_config(self.repo.as_ref(), t, e)])),
+        };
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        for source in config.proto_sources {
+            let path = BuildSystemPath::new(source, PathFormat::Posix);
+            if path.is_absolute() {
+                failed.push(FailedParent {
+                    name: path.raw.clone(),
+                    reason: "absolute paths are not allowed".to_string(),
+                    location: None,
+                });
+                continue;
+            }
+            match self.repo.resolve_rel_path(&path, t) {
+                Ok(p) => success.push(p),
+                Err(e) => failed.push(FailedParent {
+                    name: path.raw.clone(),
+                    reason: format!("{}", e),
+                    location: None,
+                }),
+            }
+        }
+        Ok((success, failed))
+    }
+}
+
+/// the paths in all `import "...";`, `import public "...";` and `import weak "...";` statements
+fn get_imports(content: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    for line in strip_comments(content).lines() {
+        let Some(rest) = line.trim().strip_prefix("import") else {
+            continue;
+        };
+        // `import` needs to be a whole word, `imported_field = 1;` is not an import
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+            continue;
+        }
+        let Some(start) = rest.find(['"', '\'']) else {
+            continue;
+        };
+        let quote = rest[start..].chars().next().unwrap();
+        let rest = &rest[start + 1..];
+        if let Some(end) = rest.find(quote) {
+            imports.push(rest[..end].to_string());
+        }
+    }
+    imports
+}
+
+/// removes `//` and `/* */` comments, string literals are kept as is
+fn strip_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            out.push(c);
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"' | '\'', _) => {
+                quote = Some(c);
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    // keep line structure intact
+                    if c == '\n' {
+                        out.push(c);
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, LinkInfer, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::{ProtoCodegenLink, ProtoInfer};
+
+    #[test]
+    fn test_infer() {
+        let us_name = "proto/orders";
+        let orders_str = r#"
+            syntax = "proto3";
+            package orders;
+
+            import "common/types.proto";
+            import public "billing/invoice.proto";
+            import "google/protobuf/timestamp.proto";
+            import "orders/internal.proto"; // our own package
+            // import "commented/out.proto";
+            /* import "also/commented.proto"; */
+            import "missing/thing.proto";
+
+            message Order { string imported_by = 1; }
+        "#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/orders.proto", us_name), orders_str.to_string()),
+                (format!("{}/internal.proto", us_name), "".to_string()),
+                (format!("{}/nabs.json", us_name), "{}".to_string()),
+                ("proto/common/types.proto".to_string(), "".to_string()),
+                ("proto/common/nabs.json".to_string(), "{}".to_string()),
+                (
+                    "vendor/schemas/billing/invoice.proto".to_string(),
+                    "".to_string(),
+                ),
+                ("vendor/schemas/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let inf = ProtoInfer::new(
+            Arc::new(repo),
+            vec!["proto".to_string(), "vendor/schemas/".to_string()],
+        );
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[&"proto/common".to_string(), &"vendor/schemas".to_string()],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(&failed_parents, &["missing/thing.proto".to_string()]);
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    #[test]
+    fn test_dot_include_root() {
+        // `protoc -I .`, imports are relative to the workspace root
+        let repo = MockRepo::new(
+            HashMap::from([
+                (
+                    "proto/orders/orders.proto".to_string(),
+                    "import \"proto/common/types.proto\";\nimport \"../outside.proto\";"
+                        .to_string(),
+                ),
+                ("proto/orders/nabs.json".to_string(), "{}".to_string()),
+                ("proto/common/types.proto".to_string(), "".to_string()),
+                ("proto/common/nabs.json".to_string(), "{}".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let repo = Arc::new(repo);
+        for roots in [vec!["."], vec!["./"], vec!["..", "./proto/.."]] {
+            let roots = roots.into_iter().map(str::to_string).collect();
+            let inf = ProtoInfer::new(Arc::clone(&repo) as _, roots);
+            let infer_result = inf
+                .from_raw_target(&RawTarget::from_string_name("proto/orders".to_string()).unwrap())
+                .unwrap();
+            let InferredTarget::One(single) = infer_result.inferred_target else {
+                panic!("expected inferred_target to be One variant");
+            };
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            assert_eq!(parents, vec!["proto/common"]);
+            let failed: Vec<&str> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect();
+            assert_eq!(failed, vec!["../outside.proto"]);
+        }
+    }
+
+    #[test]
+    fn test_codegen_link() {
+        let us_name = "services/orders";
+        let repo = MockRepo::new(
+            HashMap::from([(
+                format!("{}/nabs.json", us_name),
+                r#"{"proto_sources": ["../../proto/orders", "/abs/proto"]}"#.to_string(),
+            )]),
+            PathBuf::new(),
+        );
+        let link = ProtoCodegenLink::new(Arc::new(repo));
+        let (success, failed) = link
+            .extra_parents(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+        let success: Vec<&String> = success.iter().map(|p| p.name.to_string_ref()).collect();
+        assert_eq!(success, vec!["proto/orders"]);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, "/abs/proto");
+
+        // a broken nabs.json only loses the links declared in it
+        let repo = MockRepo::new(
+            HashMap::from([(
+                format!("{}/nabs.json", us_name),
+                "{\n  \"proto_sources\": [,]\n}".to_string(),
+            )]),
+            PathBuf::new(),
+        );
+        let link = ProtoCodegenLink::new(Arc::new(repo));
+        let (success, failed) = link
+            .extra_parents(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+        assert!(success.is_empty());
+        assert_eq!(failed[0].name, "services/orders/nabs.json");
+        assert_eq!(failed[0].location.as_ref().unwrap().line, Some(2));
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, anyhow};
+use log::info;
+use serde::Deserialize;
+
+use crate::error::NabsError;
+use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
+
+use super::core::{
+    Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents, resolve_parent,
+};
+
+pub const PUB_FLAVOR: &str = "dart_pub";
+pub const PUBSPEC_FILE_NAME: &str = "pubspec.yaml";
+
+#[derive(Debug, Deserialize)]
+struct FullDep {
+    #[serde(default)]
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Dependency {
+    Object(FullDep),
+    // version constraints (`^1.2.0`) and sdk deps without any fields (`flutter_test:`)
+    #[allow(dead_code)]
+    Other(serde_yaml::Value),
+}
+
+#[derive(Debug, Deserialize)]
+struct Pubspec {
+    #[serde(default)]
+    dependencies: Option<HashMap<String, Dependency>>,
+    #[serde(default)]
+    dev_dependencies: Option<HashMap<String, Dependency>>,
+    #[serde(default)]
+    dependency_overrides: Option<HashMap<String, Dependency>>,
+}
+
+/// infers a dart/flutter package using its `pubspec.yaml`
+/// local packages are declared as `path` dependencies in `dependencies`, `dev_dependencies` or `dependency_overrides`
+pub struct PubspecInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl PubspecInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+}
+
+impl Infer for PubspecInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![PUBSPEC_FILE_NAME.to_string()])
+    }
+
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let path = self
+            .repo
+            .target_name_to_path(&t.name)
+            .join(PUBSPEC_FILE_NAME);
+        let content = self.repo.get_content(&path)?;
+
+        match content {
+            None => Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            }),
+            Some(content) => {
+                let pubspec: Pubspec = serde_yaml::from_str(&content)
+                    .map_err(|e| NabsError::from_yaml(path.clone(), &e))?;
+                let mut success = Vec::new();
+                let mut failed = Vec::new();
+                let deps = [
+                    pubspec.dependencies,
+                    pubspec.dev_dependencies,
+                    pubspec.dependency_overrides,
+                ];
+                for dep in deps.into_iter().flatten().flat_map(|d| d.into_values()) {
+                    let Dependency::Object(FullDep { path: Some(path) }) = dep else {
+                        continue;
+                    };
+                    let p = BuildSystemPath::new(path, PathFormat::Posix);
+                    match resolve_parent(self.repo.as_ref(), &p, t) {
+                        Ok(raw_target) => {
+                            // an override usually repeats a path from `dependencies`
+                            if !success.contains(&raw_target) {
+                                success.push(raw_target);
+                            }
+                        }
+                        Err(f) => failed.push(f),
+                    }
+                }
+                locate_failed_parents(&mut failed, &path, &content);
+                info!("PubspecInfer: detected package={}", t);
+                Ok(InferResult {
+                    inferred_target: InferredTarget::One(Single {
+                        target: Target::from_raw_target(t, PUB_FLAVOR.to_string())
+                            .context(anyhow!("failed in creating target for package={}", t.name))?,
+                        parents: success,
+                        failed_parents: failed,
+                    }),
+                    what_next: Next::Continue,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::PubspecInfer;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "mobile/app";
+        let pubspec_str = r#"
+name: app
+environment:
+  sdk: ">=3.0.0 <4.0.0"
+
+dependencies:
+  flutter:
+    sdk: flutter
+  http: ^1.2.0
+  design_system:
+    path: ../design_system
+  api_client:
+    path: ../../libs/api_client
+  hosted_thing:
+    hosted: https://pub.example.com
+    version: ^2.0.0
+
+dev_dependencies:
+  flutter_test:
+    sdk: flutter
+  test_utils:
+    path: ../../../outside
+
+dependency_overrides:
+  design_system:
+    path: ../design_system
+"#;
+        let repo = MockRepo::new(
+            HashMap::from([(format!("{}/pubspec.yaml", us_name), pubspec_str.to_string())]),
+            PathBuf::new(),
+        );
+        let inf = PubspecInfer::new(Arc::new(repo));
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            compare_vec(
+                &parents,
+                &[
+                    &"mobile/design_system".to_string(),
+                    &"libs/api_client".to_string(),
+                ],
+            );
+
+            let failed_parents: Vec<String> = single
+                .failed_parents
+                .iter()
+                .map(|p| p.name.clone())
+                .collect();
+            compare_vec(&failed_parents, &["../../../outside".to_string()]);
+            let location = single.failed_parents[0].location.as_ref().unwrap();
+            assert_eq!(location.file, PathBuf::from("mobile/app/pubspec.yaml"));
+            assert_eq!((location.line, location.column), (Some(22), Some(11)));
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+
+    fn compare_vec<T: Eq>(want: &[T], got: &[T]) {
+        assert_eq!(want.len(), got.len());
+        for v in want {
+            assert!(got.contains(v));
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result, anyhow};
+use log::info;
+
+use crate::graph::EdgeKind;
+use crate::types::{RawTarget, Repository, Target};
+
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, LinkInfer, Next, Single, failed_config,
+};
+
+#[derive(Default)]
+struct ModuleIndex {
+    /// top level module name to the packages providing it
+    modules: HashMap<String, Vec<RawTarget>>,
+    /// packages whose `nabs.json` could not be read, they provide no modules
+    broken: HashMap<RawTarget, FailedParent>,
+}
+
+/// the flavor of python packages which only have source files, without `requirements.txt`
+pub const PY_SOURCES_FLAVOR: &str = "python";
+const PY_EXTENSION: &str = "py";
+const INIT_FILE_NAME: &str = "__init__.py";
+
+/// links python packages to the workspace packages they import, by scanning `import` statements in their `.py` files
+/// this is opt-in (`python.scan_imports` in `workspace.json`), the edges are marked as `EdgeKind::InferredImport`
+///
+/// top level module names are mapped to packages using an index of every package in the workspace
+/// a package provides the modules in `python_modules` of its `nabs.json`
+/// if that's not set, it provides every directory containing `__init__.py` at `<package>/<module>` or `<package>/src/<module>`
+/// a package whose root itself contains `__init__.py` provides a module with the name of its directory
+/// imports of modules not in the index are ignored, these are the standard library or third party modules
+pub struct PyImportsLink {
+    repo: Arc<dyn Repository>,
+    // built lazily, this requires walking the whole workspace
+    index: Mutex<Option<Arc<ModuleIndex>>>,
+}
+
+impl PyImportsLink {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self {
+            repo,
+            index: Mutex::new(None),
+        }
+    }
+
+    fn get_index(&self) -> Result<Arc<ModuleIndex>> {
+        // the lock is held while building, packages inferred in parallel wait for the index instead of building it again
+        let mut cached = self.index.lock().unwrap();
+        if let Some(index) = cached.as_ref() {
+            return Ok(Arc::clone(index));
+        }
+        let index = Arc::new(self.build_index()?);
+        *cached = Some(Arc::clone(&index));
+        Ok(index)
+    }
+
+    fn build_index(&self) -> Result<ModuleIndex> {
+        let mut index = ModuleIndex::default();
+        for pkg in self.repo.get_nabs_packages() {
+            let name = pkg
+                .to_str()
+                .ok_or(anyhow!("could not parse path: {:?}", pkg))?
+                .to_string();
+            let raw = RawTarget::from_string_name(name)?;
+            // a broken `nabs.json` is reported by the package itself, it shouldn't break the links of every other package
+            let config = match self.repo.package_config(&raw) {
+                Ok(config) => config,
+                Err(e) => {
+                    let failed = failed_config(self.repo.as_ref(), &raw, e);
+                    index.broken.insert(raw, failed);
+                    continue;
+                }
+            };
+            let modules = match config.python_modules {
+                Some(modules) => modules,
+                None => self.modules_of(&raw),
+            };
+            for module in modules {
+                index.modules.entry(module).or_default().push(raw.clone());
+            }
+        }
+        info!(
+            "PyImportsLink: built module index, modules={}",
+            index.modules.len()
+        );
+        Ok(index)
+    }
+
+    /// modules provided by the directories of a package, see `PyImportsLink`
+    fn modules_of(&self, t: &RawTarget) -> Vec<String> {
+        let base = self.repo.target_name_to_path(&t.name);
+        let mut modules = Vec::new();
+        for f in self.repo.package_files(t) {
+            if f.file_name().is_none_or(|n| n != INIT_FILE_NAME) {
+                continue;
+            }
+            let Some(dir) = f.parent().and_then(|d| d.strip_prefix(&base).ok()) else {
+                continue;
+            };
+            let components: Vec<&str> = dir.iter().filter_map(|c| c.to_str()).collect();
+            let module = match components.as_slice() {
+                [] => base.file_name().and_then(|n| n.to_str()),
+                [module] => Some(*module),
+                ["src", module] => Some(*module),
+                _ => None,
+            };
+            if let Some(module) = module {
+                modules.push(module.to_string());
+            }
+        }
+        modules
+    }
+}
+
+impl LinkInfer for PyImportsLink {
+    fn extra_parents(&self, t: &RawTarget) -> Result<(Vec<RawTarget>, Vec<FailedParent>)> {
+        let sources: Vec<_> = self
+            .repo
+            .package_files(t)
+            .into_iter()
+            .filter(|f| f.extension().is_some_and(|e| e == PY_EXTENSION))
+            .collect();
+        let mut success = Vec::new();
+        let mut failed = Vec::new();
+        if sources.is_empty() {
+            return Ok((success, failed));
+        }
+
+        let index = self.get_index()?;
+        if let Some(f) = index.broken.get(t) {
+            failed.push(f.clone());
+        }
+        let mut modules = HashSet::new();
+        for f in &sources {
+            if let Some(content) = self.repo.get_content(Path::new(f))? {
+                modules.extend(get_imported_modules(&content));
+            }
+        }
+        let mut modules: Vec<String> = modules.into_iter().collect();
+        modules.sort();
+        for module in modules {
+            let Some(pkgs) = index.modules.get(&module) else {
+                continue;
+            };
+            // we are allowed to import our own modules, even if someone else also provides them
+            if pkgs.contains(t) {
+                continue;
+            }
+            match pkgs.as_slice() {
+                [pkg] => success.push(pkg.clone()),
+                _ => failed.push(FailedParent {
+                    name: module,
+                    reason: format!(
+                        "module is provided by multiple packages, set `python_modules` in their nabs.json to disambiguate. packages={:?}",
+                        pkgs.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+                    ),
+                    location: None,
+                }),
+            }
+        }
+        Ok((success, failed))
+    }
+
+    /// packages were added, moved or removed, the index is built again when it is needed
+    fn reset(&self) {
+        *self.index.lock().unwrap() = None;
+    }
+
+    fn edge_kind(&self) -> EdgeKind {
+        EdgeKind::InferredImport
+    }
+}
+
+/// detects python packages which only have `.py` files, these never declare their dependencies anywhere
+/// it runs as a fallback (see `InferRunner::with_fallbacks`), a crate with a few python scripts is still a crate
+/// the package gets no parents of its own, `PyImportsLink` adds the packages it imports
+pub struct PySourcesInfer {
+    repo: Arc<dyn Repository>,
+}
+
+impl PySourcesInfer {
+    pub fn new(repo: Arc<dyn Repository>) -> Self {
+        Self { repo }
+    }
+}
+
+impl Infer for PySourcesInfer {
+    fn from_raw_target(&self, t: &RawTarget) -> Result<InferResult> {
+        let has_sources = self
+            .repo
+            .package_files(t)
+            .iter()
+            .any(|f| f.extension().is_some_and(|e| e == PY_EXTENSION));
+        if !has_sources {
+            return Ok(InferResult {
+                inferred_target: InferredTarget::Nothing,
+                what_next: Next::Continue,
+            });
+        }
+        info!("PySourcesInfer: detected package={}", t);
+        Ok(InferResult {
+            inferred_target: InferredTarget::One(Single {
+                target: Target::from_raw_target(t, PY_SOURCES_FLAVOR.to_string())
+                    .context(anyhow!("failed in creating target for package={}", t.name))?,
+                parents: Vec::new(),
+                failed_parents: Vec::new(),
+            }),
+            what_next: Next::Continue,
+        })
+    }
+}
+
+/// top level module names from `import a.b, c as d` and `from a.b import x` statements
+/// relative imports (`from . import x`) always refer to our own package and are skipped
+fn get_imported_modules(content: &str) -> Vec<String> {
+    let mut modules = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let names: Vec<&str> = if let Some(rest) = line.strip_prefix("import ") {
+            rest.split(',')
+                .filter_map(|part| part.split_whitespace().next())
+                .collect()
+        } else if let Some(rest) = line.strip_prefix("from ") {
+            rest.split_whitespace().next().into_iter().collect()
+        } else {
+            continue;
+        };
+        for name in names {
+            let top = name.split('.').next().unwrap_or_default();
+            if !top.is_empty() && top.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                modules.push(top.to_string());
+            }
+        }
+    }
+    modules
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        infer::core::LinkInfer,
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::PyImportsLink;
+
+    #[test]
+    fn test_extra_parents() {
+        let us_name = "services/api";
+        let main_str = r#"
+import os, json
+import qsync.stream as qs
+from image_utils.resize import thumbnail
+from . import handlers
+from .models import User
+import api  # our own module
+import shared
+    # import commented
+def f():
+    import lazy_mod
+        "#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                (format!("{}/nabs.json", us_name), "{}".to_string()),
+                (format!("{}/api/__init__.py", us_name), "".to_string()),
+                (format!("{}/api/main.py", us_name), main_str.to_string()),
+                ("libs/qsync/nabs.json".to_string(), "{}".to_string()),
+                (
+                    "libs/qsync/src/qsync/__init__.py".to_string(),
+                    "".to_string(),
+                ),
+                ("libs/image_utils/nabs.json".to_string(), "{}".to_string()),
+                ("libs/image_utils/__init__.py".to_string(), "".to_string()),
+                (
+                    "libs/lazy/nabs.json".to_string(),
+                    r#"{"python_modules": ["lazy_mod"]}"#.to_string(),
+                ),
+                ("libs/shared_a/nabs.json".to_string(), "{}".to_string()),
+                (
+                    "libs/shared_a/shared/__init__.py".to_string(),
+                    "".to_string(),
+                ),
+                ("libs/shared_b/nabs.json".to_string(), "{}".to_string()),
+                // a broken nabs.json only fails its own package
+                ("libs/broken/nabs.json".to_string(), "{,}".to_string()),
+                ("libs/broken/run.py".to_string(), "import qsync".to_string()),
+                (
+                    "libs/shared_b/shared/__init__.py".to_string(),
+                    "".to_string(),
+                ),
+            ]),
+            PathBuf::new(),
+        );
+        let link = PyImportsLink::new(Arc::new(repo));
+        let (success, failed) = link
+            .extra_parents(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+        let mut success: Vec<&String> = success.iter().map(|p| p.name.to_string_ref()).collect();
+        success.sort();
+        assert_eq!(success, vec!["libs/image_utils", "libs/lazy", "libs/qsync"]);
+        let failed: Vec<&String> = failed.iter().map(|f| &f.name).collect();
+        assert_eq!(failed, vec!["shared"]);
+
+        let (success, failed) = link
+            .extra_parents(&RawTarget::from_string_name("libs/broken".to_string()).unwrap())
+            .unwrap();
+        assert_eq!(success.len(), 1);
+        let failed: Vec<&String> = failed.iter().map(|f| &f.name).collect();
+        assert_eq!(failed, vec!["libs/broken/nabs.json"]);
+    }
+}
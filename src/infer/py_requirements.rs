@@ -1,22 +1,24 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use anyhow::{Context, anyhow};
 use log::info;
 
 use crate::types::{BuildSystemPath, PathFormat, RawTarget, Repository, Target};
 
-use super::core::{FailedParent, Infer, InferResult, InferredTarget, Next, Single};
+use super::core::{
+    FailedParent, Infer, InferResult, InferredTarget, Next, Single, locate_failed_parents,
+};
 
 pub const FLAVOR: &str = "python_requirements";
 pub const DEFAULT_REQ_FILE_NAME: &str = "requirements.txt";
 
 pub struct PyRequirementsInfer {
-    repo: Rc<dyn Repository>,
+    repo: Arc<dyn Repository>,
     req_file_name: String,
 }
 
 impl PyRequirementsInfer {
-    pub fn new(repo: Rc<dyn Repository>, req_file_name: String) -> Self {
+    pub fn new(repo: Arc<dyn Repository>, req_file_name: String) -> Self {
         Self {
             repo,
             req_file_name,
@@ -25,13 +27,16 @@ impl PyRequirementsInfer {
 }
 
 impl Infer for PyRequirementsInfer {
+    fn manifests(&self) -> Option<Vec<String>> {
+        Some(vec![self.req_file_name.clone()])
+    }
+
     fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<super::core::InferResult> {
-        let content = self.repo.get_content(
-            &self
-                .repo
-                .target_name_to_path(&t.name)
-                .join(&self.req_file_name),
-        );
+        let path = self
+            .repo
+            .target_name_to_path(&t.name)
+            .join(&self.req_file_name);
+        let content = self.repo.get_content(&path)?;
 
         match content {
             None => Ok(InferResult {
@@ -48,6 +53,7 @@ impl Infer for PyRequirementsInfer {
                         failed.push(FailedParent {
                             name: p.raw.clone(),
                             reason: "absolute paths are not allowed".to_string(),
+                            location: None,
                         });
                     } else {
                         let res = self.repo.resolve_rel_path(&p, t);
@@ -56,10 +62,12 @@ impl Infer for PyRequirementsInfer {
                             Err(e) => failed.push(FailedParent {
                                 name: p.raw.clone(),
                                 reason: format!("{}", e),
+                                location: None,
                             }),
                         };
                     }
                 }
+                locate_failed_parents(&mut failed, &path, &content);
                 info!("PyRequirementsInfer: detected package={}", t);
                 Ok(InferResult {
                     inferred_target: InferredTarget::One(Single {
@@ -100,7 +108,7 @@ fn get_file_names(content: &str) -> Vec<String> {
 
 #[cfg(test)]
 mod test {
-    use std::{collections::HashMap, path::PathBuf, rc::Rc};
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
     use crate::{
         infer::{
@@ -131,7 +139,7 @@ mod test {
             )]),
             PathBuf::new(),
         );
-        let inf = PyRequirementsInfer::new(Rc::new(repo), DEFAULT_REQ_FILE_NAME.to_string());
+        let inf = PyRequirementsInfer::new(Arc::new(repo), DEFAULT_REQ_FILE_NAME.to_string());
         let infer_result = inf
             .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
             .unwrap();
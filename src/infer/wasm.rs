@@ -0,0 +1,365 @@
+/// inferrers compiled to WebAssembly, declared in the `wasm_plugins` list of `workspace.json`
+/// these speak the same JSON protocol as process plugins (see `infer::plugin`), but run inside nabs in a sandbox
+/// a module has no access to the host, except reading files in the workspace through `nabs.get_content`
+///
+/// a module exports
+/// - `memory`
+/// - `alloc(len: i32) -> i32`, returns a pointer to `len` free bytes, used by nabs to pass data to the module
+/// - `infer(ptr: i32, len: i32) -> i64`, takes the request and returns the response, both utf-8 JSON
+///
+/// and can import
+/// - `nabs.get_content(ptr: i32, len: i32) -> i64`, takes a path relative to the workspace root
+///   returns `-1` if the file does not exist or the path points outside the workspace
+///
+/// every `i64` returned is a packed `(ptr << 32) | len`
+/// a new instance is created for every package, its execution is limited by fuel and its memory by `MEMORY_LIMIT`
+/// so a plugin can't hold state, loop forever or take all the memory of the host
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow};
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::config::WasmPluginConfig;
+use crate::error::NabsError;
+use crate::paths::normalize_path;
+use crate::types::{RawTarget, Repository};
+
+use super::core::{Infer, InferResult};
+use super::plugin::{PluginRequest, PluginResponse, into_infer_result};
+
+const HOST_MODULE: &str = "nabs";
+const GET_CONTENT_IMPORT: &str = "get_content";
+const MEMORY_EXPORT: &str = "memory";
+const ALLOC_EXPORT: &str = "alloc";
+const INFER_EXPORT: &str = "infer";
+const NOT_FOUND: i64 = -1;
+/// instructions a plugin is allowed to execute for a single package
+const FUEL_PER_PACKAGE: u64 = 1_000_000_000;
+/// bytes the linear memory of a plugin can grow to
+const MEMORY_LIMIT: usize = 256 << 20;
+
+pub struct WasmInfer {
+    repo: Arc<dyn Repository>,
+    config: WasmPluginConfig,
+    engine: Engine,
+    module: Module,
+    // the workspace root with symlinks resolved, `None` if it does not exist on disk
+    root: Option<PathBuf>,
+}
+
+/// the data of the store a plugin runs in
+struct Host {
+    repo: Arc<dyn Repository>,
+    root: Option<PathBuf>,
+    limits: StoreLimits,
+}
+
+impl WasmInfer {
+    pub fn new(repo: Arc<dyn Repository>, config: WasmPluginConfig) -> Result<Self> {
+        let path = repo.workspace_root().join(&config.module);
+        let bytes = std::fs::read(&path).map_err(|e| NabsError::UnreadableFile {
+            path,
+            message: e.to_string(),
+        })?;
+        Self::from_bytes(repo, config, &bytes)
+    }
+
+    fn from_bytes(
+        repo: Arc<dyn Repository>,
+        config: WasmPluginConfig,
+        bytes: &[u8],
+    ) -> Result<Self> {
+        let mut engine_config = Config::default();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config);
+        let module = Module::new(&engine, bytes)
+            .context(anyhow!("failed in compiling wasm plugin={}", config.module))?;
+        let root = repo.workspace_root().canonicalize().ok();
+        Ok(Self {
+            repo,
+            config,
+            engine,
+            module,
+            root,
+        })
+    }
+
+    fn call(&self, t: &RawTarget) -> Result<PluginResponse> {
+        let host = Host {
+            repo: Arc::clone(&self.repo),
+            root: self.root.clone(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY_LIMIT)
+                .trap_on_grow_failure(true)
+                .build(),
+        };
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        store
+            .set_fuel(FUEL_PER_PACKAGE)
+            .map_err(wasmi::Error::from)?;
+        let mut linker = <Linker<Host>>::new(&self.engine);
+        linker.func_wrap(HOST_MODULE, GET_CONTENT_IMPORT, host_get_content)?;
+        let instance = linker
+            .instantiate(&mut store, &self.module)?
+            .start(&mut store)?;
+        let memory = instance
+            .get_memory(&store, MEMORY_EXPORT)
+            .ok_or(anyhow!("wasm plugin does not export `{}`", MEMORY_EXPORT))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, ALLOC_EXPORT)?;
+        let infer = instance.get_typed_func::<(i32, i32), i64>(&store, INFER_EXPORT)?;
+
+        let root = self.repo.workspace_root().to_string_lossy().to_string();
+        let request = serde_json::to_vec(&PluginRequest {
+            package: t.name.to_string_ref(),
+            workspace_root: &root,
+        })?;
+        let ptr = alloc.call(&mut store, request.len() as i32)?;
+        memory
+            .write(&mut store, ptr as usize, &request)
+            .map_err(wasmi::Error::from)?;
+        let packed = infer
+            .call(&mut store, (ptr, request.len() as i32))
+            .context(anyhow!(
+                "wasm plugin={} failed for package={}",
+                self.config.module,
+                t
+            ))?;
+        let response = read_packed(&memory, &store, packed)?;
+        serde_json::from_slice(&response).context(anyhow!(
+            "failed in parsing response of wasm plugin={} for package={}",
+            self.config.module,
+            t
+        ))
+    }
+}
+
+impl Infer for WasmInfer {
+    fn from_raw_target(&self, t: &RawTarget) -> anyhow::Result<InferResult> {
+        let response = self.call(t)?;
+        into_infer_result(self.repo.as_ref(), t, response, &self.config.module)
+    }
+}
+
+/// `nabs.get_content`, the only way for a plugin to look at the workspace
+fn host_get_content(mut caller: Caller<'_, Host>, ptr: i32, len: i32) -> Result<i64, wasmi::Error> {
+    let memory = get_memory(&caller)?;
+    let path = read_packed(&memory, &caller, pack(ptr, len))?;
+    let path = String::from_utf8(path).map_err(to_wasm_error)?;
+    let Some(path) = sandboxed_path(&path) else {
+        return Ok(NOT_FOUND);
+    };
+    let host = caller.data();
+    if escapes_through_symlink(host, Path::new(&path)) {
+        return Ok(NOT_FOUND);
+    }
+    let content = host
+        .repo
+        .get_content(Path::new(&path))
+        .map_err(|e| to_wasm_error(format!("{:#}", e)))?;
+    let Some(content) = content else {
+        return Ok(NOT_FOUND);
+    };
+
+    let alloc = caller
+        .get_export(ALLOC_EXPORT)
+        .and_then(Extern::into_func)
+        .ok_or_else(|| wasmi::Error::new(format!("plugin does not export `{}`", ALLOC_EXPORT)))?
+        .typed::<i32, i32>(&caller)?;
+    let out = alloc.call(&mut caller, content.len() as i32)?;
+    memory.write(&mut caller, out as usize, content.as_bytes())?;
+    Ok(pack(out, content.len() as i32))
+}
+
+/// normalized version of a path requested by a plugin, `None` if it points outside the workspace
+fn sandboxed_path(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    if path
+        .components()
+        .any(|c| matches!(c, Component::RootDir | Component::Prefix(_)))
+    {
+        return None;
+    }
+    normalize_path(path)
+        .ok()
+        .and_then(|p| p.to_str().map(|s| s.to_string()))
+}
+
+/// `true` if `path` (already normalized by `sandboxed_path`) is a symlink, or is inside one, which points outside the workspace
+/// paths which don't exist on disk can't escape, the repository decides if they exist
+fn escapes_through_symlink(host: &Host, path: &Path) -> bool {
+    let Some(root) = &host.root else {
+        return false;
+    };
+    match root.join(path).canonicalize() {
+        Ok(real) => !real.starts_with(root),
+        Err(_) => false,
+    }
+}
+
+fn get_memory(caller: &Caller<'_, Host>) -> Result<Memory, wasmi::Error> {
+    caller
+        .get_export(MEMORY_EXPORT)
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new(format!("plugin does not export `{}`", MEMORY_EXPORT)))
+}
+
+fn read_packed(
+    memory: &Memory,
+    ctx: impl wasmi::AsContext,
+    packed: i64,
+) -> Result<Vec<u8>, wasmi::Error> {
+    let ptr = (packed as u64 >> 32) as usize;
+    let len = (packed as u64 & u32::MAX as u64) as usize;
+    let mut buf = vec![0; len];
+    memory.read(ctx, ptr, &mut buf)?;
+    Ok(buf)
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    (((ptr as u32 as u64) << 32) | len as u32 as u64) as i64
+}
+
+fn to_wasm_error(e: impl std::fmt::Display) -> wasmi::Error {
+    wasmi::Error::new(e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use crate::{
+        config::WasmPluginConfig,
+        infer::core::{Infer, InferredTarget, Next},
+        types::{MockRepo, RawTarget},
+    };
+
+    use super::WasmInfer;
+
+    // a bump allocator starting at 1024
+    // the plugin checks that it can't read outside the workspace, then returns `app/response.json` as the response
+    const PLUGIN_WAT: &str = r#"
+(module
+  (import "nabs" "get_content" (func $get_content (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "../secret.txt")
+  (data (i32.const 16) "app/response.json")
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "infer") (param $ptr i32) (param $len i32) (result i64)
+    (if (i64.ne (call $get_content (i32.const 0) (i32.const 13)) (i64.const -1))
+      (then unreachable))
+    (call $get_content (i32.const 16) (i32.const 17)))
+)
+"#;
+
+    #[test]
+    fn test_infer() {
+        let us_name = "app";
+        let response = r#"{
+            "target": {"flavor": "wasm"},
+            "parents": ["../lib", "../../outside"],
+            "failed_parents": [{"name": "x", "reason": "test"}]
+        }"#;
+        let repo = MockRepo::new(
+            HashMap::from([
+                ("app/response.json".to_string(), response.to_string()),
+                ("../secret.txt".to_string(), "secret".to_string()),
+            ]),
+            PathBuf::new(),
+        );
+        let inf = WasmInfer::from_bytes(
+            Arc::new(repo),
+            WasmPluginConfig {
+                module: "test.wasm".to_string(),
+            },
+            &wat::parse_str(PLUGIN_WAT).unwrap(),
+        )
+        .unwrap();
+        let infer_result = inf
+            .from_raw_target(&RawTarget::from_string_name(us_name.to_string()).unwrap())
+            .unwrap();
+
+        assert_eq!(infer_result.what_next, Next::Continue);
+        if let InferredTarget::One(single) = infer_result.inferred_target {
+            assert_eq!(single.target.name_as_string_ref(), us_name);
+            assert_eq!(single.target.flavor, "wasm");
+            let parents: Vec<&String> = single
+                .parents
+                .iter()
+                .map(|p| p.name.to_string_ref())
+                .collect();
+            assert_eq!(parents, vec!["lib"]);
+            let failed_parents: Vec<&String> =
+                single.failed_parents.iter().map(|p| &p.name).collect();
+            assert_eq!(failed_parents, vec!["x", "../../outside"]);
+        } else {
+            panic!("expected inferred_target to be One variant");
+        }
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_outside_workspace() {
+        let dir = std::env::temp_dir().join(format!("nabs-wasm-test-{}", std::process::id()));
+        let root = dir.join("ws");
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        // `app/secret.ln` looks like it is inside the workspace, its name is as long as `../secret.txt`
+        std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("app/secret.ln")).unwrap();
+        let repo = MockRepo::new(
+            HashMap::from([
+                (
+                    "app/response.json".to_string(),
+                    r#"{"target": {"flavor": "wasm"}}"#.to_string(),
+                ),
+                ("app/secret.ln".to_string(), "secret".to_string()),
+            ]),
+            root,
+        );
+        let inf = WasmInfer::from_bytes(
+            Arc::new(repo),
+            WasmPluginConfig {
+                module: "test.wasm".to_string(),
+            },
+            &wat::parse_str(PLUGIN_WAT.replace("../secret.txt", "app/secret.ln")).unwrap(),
+        )
+        .unwrap();
+        let result = inf.from_raw_target(&RawTarget::from_string_name("app".to_string()).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_ok(), "the plugin could read through the symlink");
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // asks for 512MiB, then returns a valid response
+        let wat = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{\"target\":null}")
+  (func (export "alloc") (param $len i32) (result i32) (i32.const 1024))
+  (func (export "infer") (param $ptr i32) (param $len i32) (result i64)
+    (drop (memory.grow (i32.const 8192)))
+    (i64.const 15))
+)
+"#;
+        let inf = WasmInfer::from_bytes(
+            Arc::new(MockRepo::new(HashMap::new(), PathBuf::new())),
+            WasmPluginConfig {
+                module: "test.wasm".to_string(),
+            },
+            &wat::parse_str(wat).unwrap(),
+        )
+        .unwrap();
+        let result = inf.from_raw_target(&RawTarget::from_string_name("app".to_string()).unwrap());
+        assert!(result.is_err());
+    }
+}
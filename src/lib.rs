@@ -0,0 +1,45 @@
+//! nabs tracks dependencies between the packages of a monorepo, using the manifests of their existing build tools
+//!
+//! `Workspace` loads a monorepo and builds its `TargetGraph`, which answers `rdeps`/`deps` queries
+//! custom inferrers implement `Infer` and are run using `InferRunner::new`
+//! ```no_run
+//! let workspace = nabs::Workspace::open()?;
+//! let (graph, targets) = workspace.build_graph()?;
+//! let affected = graph.rdeps(&targets[..1])?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+pub mod cache;
+pub mod ci;
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+mod diagnostics;
+pub mod error;
+pub mod exec;
+mod graph;
+pub mod infer;
+mod live;
+mod paths;
+pub mod query;
+mod reverse_index;
+pub mod rpc;
+mod types;
+mod warning;
+mod workspace;
+
+pub use diagnostics::{Diagnostic, Diagnostics, DiagnosticsFormat, Location, Severity};
+pub use error::NabsError;
+pub use graph::{EdgeKind, TargetGraph, TargetId};
+pub use infer::{
+    FailedParent, Infer, InferResult, InferRunner, InferredTarget, LinkInfer, MemoStats, Next,
+    Single,
+};
+/// an in-memory `Repository` for testing code built on nabs, enabled with the `testing` feature
+#[cfg(any(test, feature = "testing"))]
+pub use types::MockRepo;
+pub use types::{
+    BuildSystemPath, Monorepo, NABS_FILE_NAME, PathFormat, RawTarget, Repository, Target,
+    TargetName,
+};
+pub use warning::{Warning, WarningKind};
+pub use workspace::Workspace;
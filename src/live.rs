@@ -0,0 +1,403 @@
+/// a graph of the workspace which is kept up to date as files change, for long running processes like `nabs daemon`
+/// only packages whose manifests changed are inferred again (see `InferRunner::forget`)
+/// the rest of the graph is assembled again from memoized results, which takes no IO
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, Weak, mpsc},
+    time::Duration,
+};
+
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use log::{info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config::WORKSPACE_FILE_NAME;
+use crate::infer::InferRunner;
+use crate::query::Snapshot;
+use crate::types::{NABS_FILE_NAME, RawTarget, Repository};
+use crate::workspace::Workspace;
+
+/// changes are collected until nothing changes for this long, saving a file often touches it more than once
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub struct LiveGraph {
+    state: Arc<RwLock<Arc<Snapshot>>>,
+    // stops watching when dropped, the background thread only holds a weak reference to add new directories
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+impl LiveGraph {
+    /// builds the graph and starts watching the workspace in the background
+    /// `on_update` is called with every graph built after the first one
+    pub fn start(
+        repo: Arc<dyn Repository>,
+        cache: bool,
+        on_update: impl Fn(&Snapshot) + Send + 'static,
+    ) -> Result<Self> {
+        let mut builder = Builder::new(Arc::clone(&repo), cache)?;
+        let state = Arc::new(RwLock::new(Arc::new(builder.snapshot()?)));
+
+        let (tx, rx) = mpsc::channel();
+        let watcher = Arc::new(Mutex::new(notify::recommended_watcher(tx)?));
+        let mut dirs = HashSet::new();
+        watch_dirs(
+            &mut watcher.lock().unwrap(),
+            &mut dirs,
+            repo.workspace_root(),
+        )?;
+        let new_dirs = Arc::downgrade(&watcher);
+        let watched = Arc::clone(&state);
+        std::thread::spawn(move || {
+            while let Ok(event) = rx.recv() {
+                let mut changed = HashSet::new();
+                let mut event = Some(event);
+                while let Some(e) = event {
+                    if let Ok(e) = e {
+                        changed.extend(e.paths);
+                    }
+                    event = rx.recv_timeout(DEBOUNCE).ok();
+                }
+                if let Err(e) = watch_new_dirs(&new_dirs, &mut dirs, &changed) {
+                    warn!(
+                        "failed in watching new directories, changes in them are missed\n{:?}",
+                        e
+                    );
+                }
+                match builder
+                    .update(&changed)
+                    .and_then(|outdated| outdated.then(|| builder.snapshot()).transpose())
+                {
+                    Ok(Some(snapshot)) => {
+                        info!("graph updated");
+                        let snapshot = Arc::new(snapshot);
+                        *watched.write().unwrap() = Arc::clone(&snapshot);
+                        on_update(&snapshot);
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        "failed in updating the graph, queries are answered using the previous one\n{:?}",
+                        e
+                    ),
+                }
+            }
+        });
+        Ok(LiveGraph {
+            state,
+            _watcher: watcher,
+        })
+    }
+
+    /// the latest graph, it is not updated while the caller holds on to it
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.state.read().unwrap())
+    }
+}
+
+/// builds the graph and keeps it up to date, owned by the thread watching the workspace
+struct Builder {
+    workspace: Workspace,
+    cache: bool,
+    runner: InferRunner,
+    // `None` if some inferrer can't tell which files it reads, any change in a package infers it again
+    manifests: Option<GlobSet>,
+    packages: Vec<RawTarget>,
+}
+
+impl Builder {
+    fn new(repo: Arc<dyn Repository>, cache: bool) -> Result<Self> {
+        let workspace = Workspace::from_repo(repo)?.with_cache(cache);
+        let runner = workspace.runner()?;
+        let manifests = manifest_globs(&runner)?;
+        let packages = workspace.packages()?;
+        Ok(Builder {
+            workspace,
+            cache,
+            runner,
+            manifests,
+            packages,
+        })
+    }
+
+    fn snapshot(&self) -> Result<Snapshot> {
+        let (graph, _) = self.runner.build_graph(self.packages.iter().cloned())?;
+        let repo = self.workspace.repo();
+        let packages: HashSet<PathBuf> = self
+            .packages
+            .iter()
+            .map(|p| repo.target_name_to_path(&p.name))
+            .collect();
+        let warnings = self.workspace.diagnostics().take_warnings();
+        Ok(Snapshot::new(graph, packages, warnings).with_manifests(self.manifests.clone()))
+    }
+
+    /// forgets what was inferred from `changed` files (absolute paths), returns `false` if the graph is still the same
+    fn update(&mut self, changed: &HashSet<PathBuf>) -> Result<bool> {
+        let root = self.workspace.repo().workspace_root().to_path_buf();
+        let mut outdated = false;
+        for path in changed {
+            let Ok(rel) = path.strip_prefix(&root) else {
+                continue;
+            };
+            // `.nabs` has the cache and the daemon's socket, nabs writes these itself
+            if rel.starts_with(".nabs") || rel.starts_with(".git") {
+                continue;
+            }
+            if rel == Path::new(WORKSPACE_FILE_NAME) {
+                info!(
+                    "{} changed, inferring every package again",
+                    WORKSPACE_FILE_NAME
+                );
+                *self = Builder::new(Arc::clone(self.workspace.repo()), self.cache)?;
+                return Ok(true);
+            }
+            if rel.file_name().is_some_and(|n| n == NABS_FILE_NAME) {
+                info!("{} changed, inferring every package again", rel.display());
+                self.packages = self.workspace.packages()?;
+                self.runner.forget_all();
+                outdated = true;
+                continue;
+            }
+            // the manifest could belong to any directory above it, packages and the directories they depend on
+            for dir in rel.ancestors().skip(1) {
+                let Some(name) = dir.to_str().filter(|n| !n.is_empty()) else {
+                    continue;
+                };
+                let Ok(raw) = RawTarget::from_string_name(name.to_string()) else {
+                    continue;
+                };
+                let is_manifest = match (&self.manifests, rel.strip_prefix(dir)) {
+                    (None, _) => true,
+                    (Some(globs), Ok(in_dir)) => globs.is_match(in_dir),
+                    (Some(_), Err(_)) => false,
+                };
+                if is_manifest && self.runner.forget(&raw) {
+                    info!("{} changed, inferring package={} again", rel.display(), raw);
+                    outdated = true;
+                }
+            }
+        }
+        Ok(outdated)
+    }
+}
+
+/// watches every directory inside `dir` which `Repository::list_files` would look into
+/// ignored directories (like `target/` or `node_modules/`) and hidden ones (like `.git/` or `.nabs/`) can be huge and have no manifests
+/// directories are watched one by one (not recursively), so that the ignored ones are left out
+fn watch_dirs(
+    watcher: &mut RecommendedWatcher,
+    dirs: &mut HashSet<PathBuf>,
+    dir: &Path,
+) -> Result<()> {
+    for entry in ignore::WalkBuilder::new(dir).build() {
+        let entry = entry?;
+        if entry.file_type().is_some_and(|t| t.is_dir()) && dirs.insert(entry.path().to_path_buf())
+        {
+            watcher.watch(entry.path(), RecursiveMode::NonRecursive)?;
+        }
+    }
+    Ok(())
+}
+
+/// starts watching directories created inside the watched ones, unless they are ignored
+fn watch_new_dirs(
+    watcher: &Weak<Mutex<RecommendedWatcher>>,
+    dirs: &mut HashSet<PathBuf>,
+    changed: &HashSet<PathBuf>,
+) -> Result<()> {
+    // removed directories are not watched anymore
+    dirs.retain(|d| d.is_dir());
+    let Some(watcher) = watcher.upgrade() else {
+        return Ok(());
+    };
+    let mut watcher = watcher.lock().unwrap();
+    for path in changed {
+        let Some(parent) = path.parent() else {
+            continue;
+        };
+        if dirs.contains(path) || !dirs.contains(parent) || !path.is_dir() {
+            continue;
+        }
+        // the ignore rules for `path` itself are only checked when walking its parent
+        let is_ignored = !ignore::WalkBuilder::new(parent)
+            .max_depth(Some(1))
+            .build()
+            .filter_map(|e| e.ok())
+            .any(|e| e.path() == path);
+        if !is_ignored {
+            watch_dirs(&mut watcher, dirs, path)?;
+        }
+    }
+    Ok(())
+}
+
+fn manifest_globs(runner: &InferRunner) -> Result<Option<GlobSet>> {
+    let Some(manifests) = runner.manifests() else {
+        return Ok(None);
+    };
+    let mut builder = GlobSetBuilder::new();
+    for m in manifests {
+        builder.add(Glob::new(&m)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{HashMap, HashSet},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, RwLock},
+    };
+
+    use anyhow::Result;
+
+    use super::{Builder, watch_dirs, watch_new_dirs};
+    use crate::types::{Repository, TargetName};
+
+    /// same as `MockRepo`, but files can be added while the graph is live
+    struct EditableRepo {
+        files: RwLock<HashMap<String, String>>,
+        root: PathBuf,
+    }
+
+    impl Repository for EditableRepo {
+        fn get_content(&self, path: &Path) -> Result<Option<String>> {
+            Ok(self
+                .files
+                .read()
+                .unwrap()
+                .get(path.to_str().unwrap())
+                .cloned())
+        }
+
+        fn workspace_root(&self) -> &Path {
+            &self.root
+        }
+
+        fn list_files(&self, dir: &Path) -> Vec<PathBuf> {
+            self.files
+                .read()
+                .unwrap()
+                .keys()
+                .map(PathBuf::from)
+                .filter(|p| p.starts_with(dir))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_update() -> Result<()> {
+        let root = PathBuf::from("/ws");
+        let fake: HashMap<String, String> = [
+            ("a/nabs.json", ""),
+            ("a/Cargo.toml", "[package]\nname = \"a\""),
+            ("b/nabs.json", ""),
+            ("b/Cargo.toml", "[dependencies]\na = { path = \"../a\" }"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let repo: Arc<dyn Repository> = Arc::new(EditableRepo {
+            files: RwLock::new(fake),
+            root: root.clone(),
+        });
+        let mut builder = Builder::new(Arc::clone(&repo), false)?;
+        let snapshot = builder.snapshot()?;
+        let inferred = builder.runner.memo_stats().misses;
+        assert_eq!(inferred, 2);
+        let manifests = snapshot.manifests(repo.as_ref(), &TargetName::new("b".to_string())?);
+        assert_eq!(manifests, Some(vec![PathBuf::from("b/Cargo.toml")]));
+
+        // source files don't change the graph
+        let changed = [root.join("a/src/lib.rs")].into_iter().collect();
+        assert!(!builder.update(&changed)?);
+
+        // a manifest only infers its own package again
+        let changed = [
+            root.join("a/Cargo.toml"),
+            root.join(".nabs/cache/inference.json"),
+        ]
+        .into_iter()
+        .collect();
+        assert!(builder.update(&changed)?);
+        builder.snapshot()?;
+        assert_eq!(builder.runner.memo_stats().misses, inferred + 1);
+
+        // a new python package is found by the imports of the packages already in the graph
+        let fake: HashMap<String, String> = [
+            ("workspace.json", r#"{"python": {"scan_imports": true}}"#),
+            ("app/nabs.json", ""),
+            ("app/main.py", "import newlib\n"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let editable = Arc::new(EditableRepo {
+            files: RwLock::new(fake),
+            root: root.clone(),
+        });
+        let mut builder = Builder::new(Arc::clone(&editable) as _, false)?;
+        let app = TargetName::new("app".to_string())?;
+        let deps = |snapshot: &crate::query::Snapshot| -> Result<Vec<String>> {
+            Ok(snapshot
+                .deps(std::slice::from_ref(&app))?
+                .into_iter()
+                .map(|t| t.name.to_string())
+                .collect())
+        };
+        assert_eq!(deps(&builder.snapshot()?)?, vec!["app"]);
+        editable.files.write().unwrap().extend(
+            [
+                ("libs/newlib/nabs.json", ""),
+                ("libs/newlib/newlib/__init__.py", ""),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        let changed = [root.join("libs/newlib/nabs.json")].into_iter().collect();
+        assert!(builder.update(&changed)?);
+        assert_eq!(deps(&builder.snapshot()?)?, vec!["app", "libs/newlib"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_dirs() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("nabs-live-watch-{}", std::process::id()));
+        for dir in [".git", "a/src", "a/target/debug", "node_modules/x"] {
+            std::fs::create_dir_all(root.join(dir))?;
+        }
+        std::fs::write(root.join(".gitignore"), "target/\nnode_modules/\n")?;
+        let mut watcher = notify::recommended_watcher(|_| {})?;
+        let mut dirs = HashSet::new();
+        watch_dirs(&mut watcher, &mut dirs, &root)?;
+        let mut watched: Vec<PathBuf> = dirs
+            .iter()
+            .map(|d| d.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        watched.sort();
+        assert_eq!(
+            watched,
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("a"),
+                PathBuf::from("a/src")
+            ]
+        );
+
+        // a new package is watched, a new build directory is not
+        for dir in ["b/src", "b/target"] {
+            std::fs::create_dir_all(root.join(dir))?;
+        }
+        let watcher = Arc::new(Mutex::new(watcher));
+        let changed = [root.join("b"), root.join("b/target")]
+            .into_iter()
+            .collect();
+        watch_new_dirs(&Arc::downgrade(&watcher), &mut dirs, &changed)?;
+        assert!(dirs.contains(&root.join("b/src")));
+        assert!(!dirs.contains(&root.join("b/target")));
+        assert_eq!(dirs.len(), 5);
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
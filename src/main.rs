@@ -1,27 +1,82 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
-use commands::{Commands, run_command};
+use commands::{Commands, Options, run_command};
+use nabs::error::{GENERIC_EXIT_CODE, NabsError};
+use nabs::{Diagnostics, DiagnosticsFormat};
 
 mod commands;
-mod graph;
-mod infer;
-mod paths;
-mod types;
 
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// fail if any warning is found while building the graph, unless it is allowed in `workspace.json`
+    #[arg(long, global = true, visible_alias = "deny-warnings")]
+    strict: bool,
+
+    /// how warnings and errors are reported: text, json or sarif
+    /// json and sarif are written once the command is done, the output of the command itself is unchanged
+    #[arg(long, global = true, default_value = "text")]
+    diagnostics_format: DiagnosticsFormat,
+
+    /// write json or sarif diagnostics to this file instead of stderr
+    #[arg(long, global = true)]
+    diagnostics_output: Option<PathBuf>,
+
+    /// infer every package again, without reading or writing `.nabs/cache`
+    #[arg(long, global = true)]
+    no_cache: bool,
+}
+
+fn write_diagnostics(
+    diagnostics: &Diagnostics,
+    error: Option<&anyhow::Error>,
+    output: Option<&PathBuf>,
+) -> Result<()> {
+    let Some(rendered) = diagnostics.render(error)? else {
+        return Ok(());
+    };
+    match output {
+        None => eprintln!("{}", rendered),
+        Some(path) => std::fs::write(path, rendered)
+            .context(anyhow!("failed in writing diagnostics to {:?}", path))?,
+    }
+    Ok(())
 }
 
 fn main() {
     pretty_env_logger::init();
     let cli = Cli::parse();
-    match run_command(cli.command) {
-        Err(e) => {
-            eprintln!("error: {:#?}", e);
-            std::process::exit(-1);
+    let options = Options {
+        strict: cli.strict,
+        cache: !cli.no_cache,
+        diagnostics: Arc::new(Diagnostics::new(cli.diagnostics_format)),
+    };
+    let result = run_command(cli.command, &options);
+    if let Err(e) = write_diagnostics(
+        &options.diagnostics,
+        result.as_ref().err(),
+        cli.diagnostics_output.as_ref(),
+    ) {
+        eprintln!("error: {:#}", e);
+        if result.is_ok() {
+            std::process::exit(GENERIC_EXIT_CODE);
+        }
+    }
+    if let Err(e) = result {
+        match NabsError::find(&e) {
+            Some(nabs_error) => {
+                eprintln!("error[{}]: {:#}", nabs_error.code(), e);
+                std::process::exit(nabs_error.exit_code());
+            }
+            None => {
+                eprintln!("error: {:#}", e);
+                std::process::exit(GENERIC_EXIT_CODE);
+            }
         }
-        Ok(()) => {}
     }
 }
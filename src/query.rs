@@ -0,0 +1,311 @@
+/// the questions nabs answers about a built graph, shared by the CLI and `nabs daemon`
+/// everything here works on a `Snapshot`, a graph along with the packages it was built from
+/// `Request` and `Response` are the same questions as JSON, for processes which keep a graph around
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use globset::GlobSet;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{TargetGraph, TargetId};
+use crate::types::{RawTarget, Repository, Target, TargetName};
+use crate::warning::{Warning, WarningKind};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum Request {
+    /// same as `nabs changeset`, `files` are relative to the workspace root
+    Changeset { files: Vec<PathBuf> },
+    /// same as `nabs deps`
+    Deps { packages: Vec<String> },
+    /// same as `nabs why`
+    Why { from: String, to: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Response {
+    /// names of the resulting targets, in the same order the CLI prints them
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// warnings for the request, followed by the ones found while building the graph
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// the packages `files` belong to, along with a warning for every file outside all `packages`
+pub fn changed_packages(
+    files: &[PathBuf],
+    packages: &HashSet<PathBuf>,
+) -> Result<(HashSet<TargetName>, Vec<Warning>)> {
+    let mut changed = HashSet::new();
+    let mut warnings = Vec::new();
+    for f in files {
+        match which_pkg(f, packages) {
+            None => warnings.push(Warning {
+                kind: WarningKind::FileNotInPackage,
+                package: String::new(),
+                subject: f.to_string_lossy().to_string(),
+                reason: String::new(),
+                location: None,
+            }),
+            Some(v) => {
+                let v_str = v
+                    .to_str()
+                    .ok_or(anyhow!("could not parse package path: {:?}", v))?;
+                changed.insert(
+                    TargetName::new(v_str.to_string())
+                        .context(anyhow!("failed to create target for {:?}", v))?,
+                );
+            }
+        }
+    }
+    Ok((changed, warnings))
+}
+
+/// a warning for every package in `changed` without any of `targets`, inference failed for it or it was skipped
+pub fn missing_targets<'a>(
+    changed: &HashSet<TargetName>,
+    targets: impl IntoIterator<Item = &'a Target>,
+) -> Vec<Warning> {
+    let found: HashSet<&TargetName> = targets.into_iter().map(|t| &t.name).collect();
+    let mut missing: Vec<&TargetName> = changed.iter().filter(|p| !found.contains(p)).collect();
+    missing.sort_by_key(|p| p.to_string_ref());
+    missing
+        .into_iter()
+        .map(|p| Warning {
+            kind: WarningKind::PackageWithoutTarget,
+            package: p.to_string(),
+            subject: p.to_string(),
+            reason: String::new(),
+            location: None,
+        })
+        .collect()
+}
+
+fn which_pkg<'a>(p: &Path, pkgs: &'a HashSet<PathBuf>) -> Option<&'a PathBuf> {
+    p.ancestors().find_map(|a| pkgs.get(a))
+}
+
+/// a graph of the whole workspace, targets can be looked up by package name
+pub struct Snapshot {
+    graph: TargetGraph,
+    packages: HashSet<PathBuf>,
+    by_name: HashMap<TargetName, Vec<TargetId>>,
+    // files inferrers read, relative to each package, see `InferRunner::manifests`
+    manifests: Option<GlobSet>,
+    /// warnings found while building the graph
+    pub warnings: Vec<Warning>,
+}
+
+impl Snapshot {
+    pub fn new(graph: TargetGraph, packages: HashSet<PathBuf>, warnings: Vec<Warning>) -> Self {
+        let mut by_name: HashMap<TargetName, Vec<TargetId>> = HashMap::new();
+        for id in graph.ids() {
+            by_name
+                .entry(graph.target(id).name.clone())
+                .or_default()
+                .push(id);
+        }
+        Snapshot {
+            graph,
+            packages,
+            by_name,
+            manifests: None,
+            warnings,
+        }
+    }
+
+    /// `manifests` matches the files inferrers read, relative to a package
+    pub fn with_manifests(mut self, manifests: Option<GlobSet>) -> Self {
+        self.manifests = manifests;
+        self
+    }
+
+    pub fn graph(&self) -> &TargetGraph {
+        &self.graph
+    }
+
+    /// every target affected by a change in `files`, same as `nabs changeset`
+    /// the warnings are for files outside every package, and changed packages without a target
+    pub fn changeset(&self, files: &[PathBuf]) -> Result<(Vec<Target>, Vec<Warning>)> {
+        let (changed, mut warnings) = changed_packages(files, &self.packages)?;
+        let mut ids: Vec<TargetId> = changed
+            .iter()
+            .flat_map(|n| self.by_name.get(n).into_iter().flatten().copied())
+            .collect();
+        warnings.extend(missing_targets(
+            &changed,
+            ids.iter().map(|id| self.graph.target(*id)),
+        ));
+        // `changed` is a set, the order targets were added in keeps the result the same every time
+        ids.sort();
+        Ok((self.targets(self.graph.rdeps_ids(&ids)), warnings))
+    }
+
+    /// the package `file` belongs to, `file` is relative to the workspace root
+    pub fn package_of(&self, file: &Path) -> Option<&Path> {
+        which_pkg(file, &self.packages).map(|p| p.as_path())
+    }
+
+    /// the files of `package` which inferrers read, relative to the workspace root
+    /// `None` if the inferrers can't tell, or if the snapshot was built without `with_manifests`
+    pub fn manifests(&self, repo: &dyn Repository, package: &TargetName) -> Option<Vec<PathBuf>> {
+        let globs = self.manifests.as_ref()?;
+        let base = repo.target_name_to_path(package);
+        let mut files: Vec<PathBuf> = repo
+            .package_files(&RawTarget::new(package.clone()))
+            .into_iter()
+            .filter(|f| f.strip_prefix(&base).is_ok_and(|rel| globs.is_match(rel)))
+            .collect();
+        files.sort();
+        Some(files)
+    }
+
+    /// every target depending on `packages`, transitively, including their own targets
+    pub fn rdeps(&self, packages: &[TargetName]) -> Result<Vec<Target>> {
+        let ids = self.ids(packages)?;
+        Ok(self.targets(self.graph.rdeps_ids(&ids)))
+    }
+
+    /// every target `packages` depend on, transitively, including their own targets
+    pub fn deps(&self, packages: &[TargetName]) -> Result<Vec<Target>> {
+        let ids = self.ids(packages)?;
+        Ok(self.targets(self.graph.deps_ids(&ids)))
+    }
+
+    /// the shortest chain of dependents from `from` to `to`, this is why a change in `from` affects `to`
+    /// fails if `to` does not depend on `from`
+    pub fn why(&self, from: &TargetName, to: &TargetName) -> Result<Vec<Target>> {
+        let from_ids = self.ids(std::slice::from_ref(from))?;
+        let to_ids = self.ids(std::slice::from_ref(to))?;
+        match self.graph.path_ids(&from_ids, &to_ids) {
+            Some(path) => Ok(self.targets(path)),
+            None => bail!("package={} does not depend on package={}", to, from),
+        }
+    }
+
+    /// answers a request, failures are reported in `Response::error`
+    pub fn answer(&self, request: Request) -> Response {
+        let result = match request {
+            Request::Changeset { files } => self.changeset(&files),
+            Request::Deps { packages } => names(&packages)
+                .and_then(|p| self.deps(&p))
+                .map(|t| (t, Vec::new())),
+            Request::Why { from, to } => names(&[from, to])
+                .and_then(|n| self.why(&n[0], &n[1]))
+                .map(|t| (t, Vec::new())),
+        };
+        match result {
+            Ok((targets, mut warnings)) => {
+                warnings.extend(self.warnings.iter().cloned());
+                Response {
+                    packages: targets.iter().map(|t| t.name.to_string()).collect(),
+                    warnings,
+                    error: None,
+                }
+            }
+            Err(e) => Response {
+                error: Some(format!("{:#}", e)),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn ids(&self, packages: &[TargetName]) -> Result<Vec<TargetId>> {
+        let mut ids = Vec::new();
+        for p in packages {
+            let found = self
+                .by_name
+                .get(p)
+                .ok_or_else(|| anyhow!("package={} is not in the graph", p))?;
+            ids.extend(found);
+        }
+        Ok(ids)
+    }
+
+    fn targets(&self, ids: Vec<TargetId>) -> Vec<Target> {
+        ids.into_iter()
+            .map(|id| self.graph.target(id).clone())
+            .collect()
+    }
+}
+
+fn names(packages: &[String]) -> Result<Vec<TargetName>> {
+    packages
+        .iter()
+        .map(|p| TargetName::new(p.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use anyhow::Result;
+
+    use super::{Request, Snapshot};
+    use crate::graph::{EdgeKind, TargetGraph};
+    use crate::types::{Target, TargetName};
+    use crate::warning::WarningKind;
+
+    #[test]
+    fn test_snapshot() -> Result<()> {
+        let target = |n: &str| Target::from_string_name(n.to_string(), "cargo".to_string());
+        let (lib, core, app) = (target("libs/lib")?, target("libs/core")?, target("app")?);
+        let mut g = TargetGraph::new();
+        for t in [&lib, &core, &app] {
+            g.add_node(t.clone());
+        }
+        // libs/lib -> libs/core -> app
+        g.add_edge(&lib, &core, EdgeKind::Declared)?;
+        g.add_edge(&core, &app, EdgeKind::Declared)?;
+        // `libs/broken` is a package, but inference failed for it
+        let packages: HashSet<PathBuf> = ["libs/lib", "libs/core", "app", "libs/broken"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let snapshot = Snapshot::new(g, packages, Vec::new());
+
+        let (affected, warnings) = snapshot.changeset(&[
+            PathBuf::from("libs/core/src/lib.rs"),
+            PathBuf::from("README.md"),
+        ])?;
+        assert_eq!(affected, vec![core.clone(), app.clone()]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::FileNotInPackage);
+        let (affected, warnings) =
+            snapshot.changeset(&[PathBuf::from("libs/broken/src/lib.rs")])?;
+        assert!(affected.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::PackageWithoutTarget);
+        assert_eq!(warnings[0].package, "libs/broken");
+
+        let name = |n: &str| TargetName::new(n.to_string());
+        assert_eq!(
+            snapshot.deps(&[name("libs/core")?])?,
+            vec![core.clone(), lib.clone()]
+        );
+        assert_eq!(
+            snapshot.why(&name("libs/lib")?, &name("app")?)?,
+            vec![lib.clone(), core.clone(), app.clone()]
+        );
+        assert!(snapshot.why(&name("app")?, &name("libs/lib")?).is_err());
+        assert!(snapshot.deps(&[name("nope")?]).is_err());
+
+        let request =
+            serde_json::from_str(r#"{"method": "why", "from": "libs/lib", "to": "app"}"#)?;
+        let response = snapshot.answer(request);
+        assert_eq!(response.packages, vec!["libs/lib", "libs/core", "app"]);
+        assert!(response.error.is_none());
+        let response = snapshot.answer(Request::Deps {
+            packages: vec!["nope".to_string()],
+        });
+        assert!(response.error.is_some());
+        Ok(())
+    }
+}
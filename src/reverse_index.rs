@@ -0,0 +1,266 @@
+/// a cheap over-approximation of the reverse dependencies of every package, built without running any inferrer
+/// every word in a package's manifests which looks like a path is taken as a dependency on the package it points into
+/// paths are tried relative to the package (`../lib`, `path:./lib`), to the manifest's own directory (for manifests in a subdirectory of the package)
+/// and to the workspace root (`//libs/lib:lib`)
+/// this finds every dependency the inferrers can find, along with many which don't exist
+/// dependencies on directories without `nabs.json` are not followed
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, anyhow};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+
+use crate::types::{RawTarget, Repository};
+
+pub struct ReverseIndex {
+    // package -> packages whose manifests mention it
+    dependents: HashMap<RawTarget, Vec<RawTarget>>,
+    // packages whose manifests could not be read, these could depend on anything
+    unknown: Vec<RawTarget>,
+}
+
+impl ReverseIndex {
+    /// scans the files matching `manifests` (globs relative to each package, see `InferRunner::manifests`) in every package
+    pub fn build(
+        repo: &dyn Repository,
+        packages: &[RawTarget],
+        manifests: &[String],
+    ) -> Result<Self> {
+        let names: HashMap<&str, &RawTarget> = packages
+            .iter()
+            .map(|p| (p.name.to_string_ref().as_str(), p))
+            .collect();
+        let (literal, globs): (Vec<&String>, Vec<&String>) = manifests
+            .iter()
+            .partition(|m| !m.contains(['*', '?', '[', '{']));
+        let mut builder = GlobSetBuilder::new();
+        for g in globs {
+            builder.add(Glob::new(g).map_err(|e| anyhow!("invalid manifest glob={}, {}", g, e))?);
+        }
+        let globs = builder.build()?;
+
+        let mentions: Vec<(&RawTarget, Option<HashSet<&RawTarget>>)> = packages
+            .par_iter()
+            .map(|p| {
+                let mentioned = mentioned_packages(repo, p, &literal, &globs, &names).ok();
+                (p, mentioned)
+            })
+            .collect();
+
+        let mut index = ReverseIndex {
+            dependents: HashMap::new(),
+            unknown: Vec::new(),
+        };
+        for (p, mentioned) in mentions {
+            let Some(mentioned) = mentioned else {
+                index.unknown.push(p.clone());
+                continue;
+            };
+            for m in mentioned {
+                index
+                    .dependents
+                    .entry(m.clone())
+                    .or_default()
+                    .push(p.clone());
+            }
+        }
+        Ok(index)
+    }
+
+    /// `changed` along with every package which could depend on them, transitively
+    pub fn dependents(&self, changed: &[RawTarget]) -> HashSet<RawTarget> {
+        let mut seen: HashSet<RawTarget> = HashSet::new();
+        let mut stack: Vec<&RawTarget> = changed.iter().chain(self.unknown.iter()).collect();
+        while let Some(p) = stack.pop() {
+            if !seen.insert(p.clone()) {
+                continue;
+            }
+            stack.extend(self.dependents.get(p).into_iter().flatten());
+        }
+        seen
+    }
+}
+
+/// the packages mentioned in the manifests of `p`, other than `p` itself
+fn mentioned_packages<'a>(
+    repo: &dyn Repository,
+    p: &RawTarget,
+    literal: &[&String],
+    globs: &GlobSet,
+    names: &HashMap<&str, &'a RawTarget>,
+) -> Result<HashSet<&'a RawTarget>> {
+    let base = repo.target_name_to_path(&p.name);
+    let mut files: Vec<_> = literal.iter().map(|m| base.join(m)).collect();
+    if !globs.is_empty() {
+        files.extend(
+            repo.package_files(p)
+                .into_iter()
+                .filter(|f| f.strip_prefix(&base).is_ok_and(|rel| globs.is_match(rel))),
+        );
+    }
+
+    let name = p.name.to_string_ref().as_str();
+    let mut mentioned = HashSet::new();
+    for f in files {
+        let Some(content) = repo.get_content(&f)? else {
+            continue;
+        };
+        // inferrers like `ConfiguredInfer` resolve paths against the directory of a nested manifest
+        let dir = f
+            .parent()
+            .and_then(|d| d.to_str())
+            .map(|d| d.replace('\\', "/"))
+            .filter(|d| d != name);
+        for word in content
+            .split(|c| !is_path_char(c))
+            .filter(|w| !w.is_empty())
+        {
+            let word = word.replace('\\', "/");
+            let in_dir = dir.as_deref().and_then(|d| join(d, &word));
+            for path in [join(name, &word), in_dir, join("", &word)]
+                .into_iter()
+                .flatten()
+            {
+                if let Some(pkg) = enclosing(names, &path)
+                    && *pkg != *p
+                {
+                    mentioned.insert(pkg);
+                }
+            }
+        }
+    }
+    Ok(mentioned)
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | '\\' | '+' | '~')
+}
+
+/// `rel` relative to `base`, with `.`, `..` and empty components removed
+/// `None` if it goes above the workspace root
+fn join(base: &str, rel: &str) -> Option<String> {
+    let mut parts: Vec<&str> = base.split('/').filter(|c| !c.is_empty()).collect();
+    for c in rel.split('/') {
+        match c {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            c => parts.push(c),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// the closest package containing `path`
+fn enclosing<'a>(names: &HashMap<&str, &'a RawTarget>, path: &str) -> Option<&'a RawTarget> {
+    let mut cur = Some(path);
+    while let Some(p) = cur {
+        if let Some(pkg) = names.get(p) {
+            return Some(pkg);
+        }
+        cur = p.rsplit_once('/').map(|(parent, _)| parent);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    use anyhow::Result;
+
+    use super::ReverseIndex;
+    use crate::types::{MockRepo, RawTarget, Repository};
+    use crate::workspace::Workspace;
+
+    #[test]
+    fn test_dependents() -> Result<()> {
+        let fake: HashMap<String, String> = [
+            (
+                "workspace.json",
+                r#"{
+                    "enable": {"bazel": true, "gemfile": true},
+                    "inferrers": [{
+                        "flavor": "dotnet",
+                        "manifest": "**/*.csproj",
+                        "pattern": "Include=\"([^\"]+)\""
+                    }]
+                }"#,
+            ),
+            ("libs/core/nabs.json", ""),
+            ("libs/core/Cargo.toml", "[package]\nname = \"core\""),
+            ("libs/util/nabs.json", ""),
+            (
+                "libs/util/Cargo.toml",
+                "[dependencies]\ncore = { path = \"../core\" }",
+            ),
+            ("libs/net/nabs.json", ""),
+            (
+                "libs/net/BUILD",
+                "rust_library(deps = [\"//libs/util:util\"])",
+            ),
+            ("apps/web/nabs.json", ""),
+            ("apps/web/requirements.txt", "../../libs/net\nrequests==2.0"),
+            ("apps/cli/nabs.json", ""),
+            ("apps/cli/Gemfile", "gem \"rails\""),
+            // mentions core, but only in a file no inferrer reads
+            ("apps/cli/README.md", "see ../../libs/core"),
+            ("dotnet/Core/nabs.json", ""),
+            ("dotnet/Core/Core.csproj", ""),
+            // only a nested manifest depends on Core, relative to its own directory
+            ("dotnet/app/nabs.json", ""),
+            ("dotnet/app/App.csproj", ""),
+            (
+                "dotnet/app/tests/Tests.csproj",
+                r#"<ProjectReference Include="../../Core/Core.csproj" />"#,
+            ),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(fake, PathBuf::new()));
+        let workspace = Workspace::from_repo(Arc::clone(&repo))?;
+        let packages = workspace.packages()?;
+        let manifests = workspace.runner()?.manifests().unwrap();
+        let index = ReverseIndex::build(repo.as_ref(), &packages, &manifests)?;
+
+        let mut got: Vec<String> = index
+            .dependents(&[RawTarget::from_string_name("libs/util".to_string())?])
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect();
+        got.sort();
+        assert_eq!(got, vec!["apps/web", "libs/net", "libs/util"]);
+
+        // the lazy graph has the same reverse dependencies as the whole graph
+        let rdeps = |package: &str| -> Result<Vec<String>> {
+            let changed = [RawTarget::from_string_name(package.to_string())?];
+            let mut rdeps = Vec::new();
+            for (graph, targets) in [
+                workspace.build_graph()?,
+                workspace.build_rdeps_graph(&changed)?,
+            ] {
+                let ours: Vec<_> = targets
+                    .into_iter()
+                    .filter(|t| t.name == changed[0].name)
+                    .collect();
+                let mut names: Vec<String> = graph
+                    .rdeps(&ours)?
+                    .into_iter()
+                    .map(|t| t.name.to_string())
+                    .collect();
+                names.sort();
+                rdeps.push(names);
+            }
+            assert_eq!(rdeps[0], rdeps[1]);
+            Ok(rdeps.remove(0))
+        };
+        assert_eq!(
+            rdeps("libs/core")?,
+            vec!["apps/web", "libs/core", "libs/net", "libs/util"]
+        );
+        assert_eq!(rdeps("dotnet/Core")?, vec!["dotnet/Core", "dotnet/app"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+/// `nabs serve --stdio` answers graph queries from editors using JSON-RPC 2.0
+/// messages are framed with a `Content-Length` header, the same way as the language server protocol
+///
+/// | method | params | result |
+/// |--------|--------|--------|
+/// | `nabs/packageOf` | `{"file": "libs/core/src/lib.rs"}` | `{"package": "libs/core", "manifests": ["libs/core/Cargo.toml"]}` |
+/// | `nabs/rdeps` | `{"packages": ["libs/core"]}` | `{"packages": ["libs/core", "apps/web"]}` |
+/// | `nabs/deps` | `{"packages": ["apps/web"]}` | `{"packages": ["apps/web", "libs/core"]}` |
+/// | `nabs/graph` | | `{"targets": [{"name": "libs/core", "flavor": "cargo"}], "edges": [{"from": 0, "to": 1, "kind": "declared"}]}` |
+/// | `nabs/diagnostics` | | the warnings of the graph, in the same format as `--diagnostics-format json` |
+///
+/// the graph is kept up to date as files change, a `nabs/graphChanged` notification is sent after every update
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result, anyhow};
+use log::info;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::diagnostics::Diagnostic;
+use crate::live::LiveGraph;
+use crate::query::Snapshot;
+use crate::types::{Repository, Target, TargetName};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// the query could not be answered, like a package which is not in the graph
+const QUERY_FAILED: i64 = -32000;
+
+const METHODS: [&str; 5] = [
+    "nabs/packageOf",
+    "nabs/rdeps",
+    "nabs/deps",
+    "nabs/graph",
+    "nabs/diagnostics",
+];
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: String) -> Self {
+        RpcError { code, message }
+    }
+}
+
+#[derive(Deserialize)]
+struct FileParams {
+    /// relative to the workspace root, absolute or a `file://` uri
+    file: String,
+}
+
+#[derive(Deserialize)]
+struct PackagesParams {
+    packages: Vec<String>,
+}
+
+/// answers requests read from `input` until it is closed or an `exit` notification is received
+pub fn serve(
+    repo: Arc<dyn Repository>,
+    cache: bool,
+    mut input: impl BufRead,
+    output: impl Write + Send + 'static,
+) -> Result<()> {
+    let output = Arc::new(Mutex::new(output));
+    let notifications = Arc::clone(&output);
+    let graph = LiveGraph::start(Arc::clone(&repo), cache, move |snapshot| {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "method": "nabs/graphChanged",
+            "params": {
+                "targets": snapshot.graph().len(),
+                "warnings": snapshot.warnings.len(),
+            },
+        });
+        if let Err(e) = write_message(&mut *notifications.lock().unwrap(), &message) {
+            info!("failed in sending nabs/graphChanged, {:#}", e);
+        }
+    })?;
+    let server = Server { repo, graph };
+
+    while let Some(message) = read_message(&mut input)? {
+        let reply = match serde_json::from_str::<Value>(&message) {
+            Err(e) => Some(reply(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, e.to_string())),
+            )),
+            Ok(request) => {
+                let method = request["method"].as_str().unwrap_or_default();
+                match request.get("id") {
+                    // notifications are not answered
+                    None if method == "exit" => break,
+                    None => None,
+                    Some(id) => Some(reply(
+                        id.clone(),
+                        server.handle(method, request.get("params").cloned()),
+                    )),
+                }
+            }
+        };
+        if let Some(reply) = reply {
+            write_message(&mut *output.lock().unwrap(), &reply)?;
+        }
+    }
+    Ok(())
+}
+
+fn reply(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": e.code, "message": e.message},
+        }),
+    }
+}
+
+/// the body of the next message, `None` once `input` is closed
+fn read_message(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .context(anyhow!("invalid header={}", header))?,
+            );
+        }
+    }
+    let length = length.ok_or(anyhow!("message without a Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8(body)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
+
+struct Server {
+    repo: Arc<dyn Repository>,
+    graph: LiveGraph,
+}
+
+impl Server {
+    fn handle(&self, method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        let snapshot = self.graph.snapshot();
+        match method {
+            "initialize" => Ok(json!({
+                "serverInfo": {"name": "nabs", "version": env!("CARGO_PKG_VERSION")},
+                "capabilities": {"methods": METHODS},
+            })),
+            "shutdown" => Ok(Value::Null),
+            "nabs/packageOf" => {
+                let FileParams { file } = parse(params)?;
+                let Some(package) = self.relative(&file).and_then(|f| {
+                    snapshot
+                        .package_of(&f)
+                        .and_then(|p| p.to_str())
+                        .map(str::to_string)
+                }) else {
+                    return Ok(json!({"package": null, "manifests": []}));
+                };
+                let manifests = TargetName::new(package.clone())
+                    .ok()
+                    .and_then(|name| snapshot.manifests(self.repo.as_ref(), &name))
+                    .unwrap_or_default();
+                Ok(json!({"package": package, "manifests": manifests}))
+            }
+            "nabs/rdeps" => {
+                let PackagesParams { packages } = parse(params)?;
+                query(packages, |names| snapshot.rdeps(names))
+            }
+            "nabs/deps" => {
+                let PackagesParams { packages } = parse(params)?;
+                query(packages, |names| snapshot.deps(names))
+            }
+            "nabs/graph" => Ok(dump(&snapshot)),
+            "nabs/diagnostics" => {
+                let diagnostics: Vec<Diagnostic> = snapshot
+                    .warnings
+                    .iter()
+                    .map(Diagnostic::from_warning)
+                    .collect();
+                Ok(json!(diagnostics))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method={}", method),
+            )),
+        }
+    }
+
+    /// `file` relative to the workspace root, `None` if it is outside the workspace or an invalid uri
+    fn relative(&self, file: &str) -> Option<PathBuf> {
+        let file = if file.starts_with("file://") {
+            // editors percent-encode uris, like a space as `%20`
+            Url::parse(file).ok()?.to_file_path().ok()?
+        } else {
+            PathBuf::from(file)
+        };
+        if file.is_absolute() {
+            file.strip_prefix(self.repo.workspace_root())
+                .ok()
+                .map(Path::to_path_buf)
+        } else {
+            Some(file.to_path_buf())
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Option<Value>) -> Result<T, RpcError> {
+    serde_json::from_value(params.unwrap_or(Value::Null))
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn query(
+    packages: Vec<String>,
+    f: impl FnOnce(&[TargetName]) -> Result<Vec<Target>>,
+) -> Result<Value, RpcError> {
+    packages
+        .into_iter()
+        .map(TargetName::new)
+        .collect::<Result<Vec<_>>>()
+        .and_then(|names| f(&names))
+        .map(|targets| {
+            let names: Vec<String> = targets.iter().map(|t| t.name.to_string()).collect();
+            json!({ "packages": names })
+        })
+        .map_err(|e| RpcError::new(QUERY_FAILED, format!("{:#}", e)))
+}
+
+/// every target, and every edge as positions in the list of targets
+fn dump(snapshot: &Snapshot) -> Value {
+    let graph = snapshot.graph();
+    let targets: Vec<Value> = graph
+        .ids()
+        .map(|id| {
+            let t = graph.target(id);
+            json!({"name": t.name.to_string(), "flavor": t.flavor})
+        })
+        .collect();
+    let edges: Vec<Value> = graph
+        .edges()
+        .map(|(from, to, kind)| {
+            json!({"from": from.index(), "to": to.index(), "kind": kind.to_string()})
+        })
+        .collect();
+    json!({"targets": targets, "edges": edges})
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        fs,
+        io::{BufReader, Cursor, Write},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use anyhow::Result;
+    use serde_json::{Value, json};
+    use url::Url;
+
+    use super::{METHOD_NOT_FOUND, QUERY_FAILED, Server, read_message, serve, write_message};
+    use crate::live::LiveGraph;
+    use crate::types::{MockRepo, Repository};
+
+    #[test]
+    fn test_framing() -> Result<()> {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({"id": 1}))?;
+        write_message(&mut out, &json!({"id": 2}))?;
+        assert!(out.starts_with(b"Content-Length: 8\r\n\r\n{\"id\":1}"));
+
+        let mut input = Cursor::new(out);
+        assert_eq!(read_message(&mut input)?.as_deref(), Some("{\"id\":1}"));
+        assert_eq!(read_message(&mut input)?.as_deref(), Some("{\"id\":2}"));
+        assert_eq!(read_message(&mut input)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle() -> Result<()> {
+        // the workspace is watched, so its root has to exist
+        // the space is percent-encoded in uris
+        let root = std::env::temp_dir().join(format!("nabs rpc-test-{}", std::process::id()));
+        fs::create_dir_all(&root)?;
+        let fake: HashMap<String, String> = [
+            ("libs/core/nabs.json", ""),
+            ("libs/core/Cargo.toml", "[package]\nname = \"core\""),
+            ("apps/web/nabs.json", ""),
+            ("apps/web/requirements.txt", "../../libs/core"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(fake, root.clone()));
+        let graph = LiveGraph::start(Arc::clone(&repo), false, |_| {})?;
+        let server = Server { repo, graph };
+        let call = |method: &str, params: Value| server.handle(method, Some(params));
+
+        let file = root.join("libs/core/src/lib.rs");
+        let got = call("nabs/packageOf", json!({"file": file})).unwrap();
+        assert_eq!(
+            got,
+            json!({"package": "libs/core", "manifests": ["libs/core/Cargo.toml"]})
+        );
+        let uri = Url::from_file_path(&file).unwrap();
+        assert!(uri.as_str().contains("%20"));
+        let got = call("nabs/packageOf", json!({"file": uri.as_str()})).unwrap();
+        assert_eq!(got["package"], "libs/core");
+        let got = call("nabs/packageOf", json!({"file": "README.md"})).unwrap();
+        assert_eq!(got["package"], Value::Null);
+
+        let got = call("nabs/rdeps", json!({"packages": ["libs/core"]})).unwrap();
+        assert_eq!(got, json!({"packages": ["libs/core", "apps/web"]}));
+        let got = call("nabs/deps", json!({"packages": ["apps/web"]})).unwrap();
+        assert_eq!(got, json!({"packages": ["apps/web", "libs/core"]}));
+        let got = call("nabs/graph", Value::Null).unwrap();
+        assert_eq!(got["targets"].as_array().unwrap().len(), 2);
+        assert_eq!(got["edges"].as_array().unwrap().len(), 1);
+
+        let err = call("nabs/deps", json!({"packages": ["nope"]})).unwrap_err();
+        assert_eq!(err.code, QUERY_FAILED);
+        let err = call("nabs/nope", Value::Null).unwrap_err();
+        assert_eq!(err.code, METHOD_NOT_FOUND);
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    /// everything written by the server, shared with the test
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn contains(&self, s: &str) -> bool {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).contains(s)
+        }
+
+        /// waits until the output contains `s`, `false` if it doesn't after a few seconds
+        fn wait_for(&self, s: &str) -> bool {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_secs(10) {
+                if self.contains(s) {
+                    return true;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            false
+        }
+    }
+
+    #[test]
+    fn test_graph_changed() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("nabs-rpc-changed-{}", std::process::id()));
+        fs::create_dir_all(root.join("libs/core"))?;
+        let fake: HashMap<String, String> = [
+            ("libs/core/nabs.json", ""),
+            ("libs/core/Cargo.toml", "[package]\nname = \"core\""),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let repo: Arc<dyn Repository> = Arc::new(MockRepo::new(fake, root.clone()));
+        let (reader, mut writer) = std::io::pipe()?;
+        let output = Output::default();
+        let server = {
+            let output = output.clone();
+            std::thread::spawn(move || serve(repo, false, BufReader::new(reader), output))
+        };
+
+        // once the server answers, the workspace is being watched
+        let mut request = Vec::new();
+        write_message(
+            &mut request,
+            &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"}),
+        )?;
+        writer.write_all(&request)?;
+        assert!(output.wait_for("\"id\":1"));
+        assert!(!output.contains("nabs/graphChanged"));
+
+        fs::write(
+            root.join("libs/core/Cargo.toml"),
+            "[package]\nname = \"core\"",
+        )?;
+        assert!(output.wait_for("nabs/graphChanged"));
+
+        drop(writer);
+        server.join().unwrap()?;
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
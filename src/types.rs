@@ -15,14 +15,27 @@
 /// That node in graph should be invalidated in this case (not handled right now)
 /// Finally `Repository` is the trait which allows us to play with the repository (it could be FS or just a mock implementation)
 /// This trait provides many primitives to inter-convert all our path representations
+#[cfg(any(test, feature = "testing"))]
+use std::collections::HashMap;
 use std::{
-    collections::HashMap, fmt::Display, io::ErrorKind, path::{Path, PathBuf}
+    fmt::Display,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use anyhow::{Context, Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow};
 use log::info;
+use serde::Deserialize;
 
+use crate::config::{PackageConfig, WORKSPACE_FILE_NAME, WorkspaceConfig, parse_config};
+use crate::diagnostics::Diagnostics;
+use crate::error::NabsError;
 use crate::paths::{normalize_path, posix_to_win};
+use crate::warning::{Warning, WarningKind};
+
+/// the file which marks a directory as a nabs package
+pub const NABS_FILE_NAME: &str = "nabs.json";
 
 /// `TargetName` is the format nabs uses to uniquely identify a package in the monorepo
 /// the format is simply a posix based string. It can only be a relative path from the root of the monorepo
@@ -49,28 +62,31 @@ impl TargetName {
         &self.0
     }
 
-    fn validate(name: &str) -> Result<()> {
+    fn validate(name: &str) -> Result<(), NabsError> {
         // validate that name is a valid relative posix path
         // but does not contain . or .. components
+        let invalid = |reason: &str| NabsError::InvalidTargetName {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        };
         if name.is_empty() {
-            bail!("target name cannot be empty".to_string());
+            return Err(invalid("target name cannot be empty"));
         }
 
         // check for absolute paths
         if name.starts_with('/') {
-            bail!("target name cannot be absolute path: name={}", name);
+            return Err(invalid("target name cannot be absolute path"));
         }
 
         // split into path components and validate each
         for component in name.split('/') {
             if component.is_empty() {
-                bail!(
-                    "detected target name with multiple slashes (like this: //). This is not allowed. name={}",
-                    name
-                );
+                return Err(invalid(
+                    "detected target name with multiple slashes (like this: //). This is not allowed",
+                ));
             }
             if component == "." || component == ".." {
-                bail!("'.' and '..' not allowed in target name, name={}", name);
+                return Err(invalid("'.' and '..' not allowed in target name"));
             }
         }
 
@@ -147,7 +163,10 @@ impl Display for RawTarget {
 /// These generally end up being simply posix paths
 /// The other format I can think of is simply using the host path format
 /// This enum is used to differentiate between the two
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PathFormat {
+    #[default]
     Posix,
     Host,
 }
@@ -191,46 +210,62 @@ impl BuildSystemPath {
 /// note: the fact that it models a file system backed repository is not abstracted out, I'm adding this here cuz all methods work with paths or talk about reading files, etc
 /// This trait is also responsible for providing ways to convert all our different path string formats
 /// I'm keeping the API very concrete and explicit, its very confusing right now anyways, not touching the whole `From` business for now
+/// packages are inferred in parallel, so a repository is shared between threads
 ///
-pub trait Repository {
+pub trait Repository: Send + Sync {
     /// given a path provide the content corresponding to that "path"
     /// for a FS repository, this would simply involve reading the file at the path
     /// if the path is not found, return `None`
-    /// NOTE: this might change to `Result` type later
-    fn get_content(&self, path: &Path) -> Option<String>;
+    /// fails with `NabsError::UnreadableFile` if the file exists but can't be read (permissions, not utf-8)
+    fn get_content(&self, path: &Path) -> Result<Option<String>>;
 
     /// return the root of the monorepo
     fn workspace_root(&self) -> &Path;
 
-    fn get_nabs_packages(&self) -> Vec<PathBuf> {
-        let nabs_pkgs: Vec<PathBuf> = ignore::Walk::new(self.workspace_root()).into_iter().filter_map(|v| {
-            match v {
-                Err(e) => {
-                    eprintln!("warning: nabs could not read path, skipping analysis for this path and its children. cause={}", e);
-                    None
-                },
-                Ok(entry) => {
-                    let p = entry.path();
-                    if p.is_file() {
-                        match p.file_name() {
-                            None => None,
-                            Some(v) => {
-                                if v == "nabs.json" {
-                                    PathBuf::from(p).parent().map(|p| PathBuf::from(p.strip_prefix(self.workspace_root()).unwrap()))
-                                } else {
-                                    None
-                                }
-                            },
-                        }
-                        // Some(p)
-                    } else {
-                        None
-                    }
+    /// list all files inside `dir` recursively, `dir` and the returned paths are relative to the workspace root
+    /// a repository can choose to skip ignored files (like the ones in `.gitignore`)
+    fn list_files(&self, dir: &Path) -> Vec<PathBuf>;
+
+    /// all files belonging to the package, files inside nested packages are skipped
+    fn package_files(&self, t: &RawTarget) -> Vec<PathBuf> {
+        let files = self.list_files(&self.target_name_to_path(&t.name));
+        let nested: Vec<&Path> = files
+            .iter()
+            .filter(|f| f.file_name().is_some_and(|n| n == NABS_FILE_NAME))
+            .filter_map(|f| f.parent())
+            .filter(|d| *d != self.target_name_to_path(&t.name))
+            .collect();
+        files
+            .iter()
+            .filter(|f| !nested.iter().any(|d| f.starts_with(d)))
+            .cloned()
+            .collect()
+    }
 
-                },
-            }
-        }).collect();
-        nabs_pkgs
+    /// the parsed `workspace.json`
+    fn workspace_config(&self) -> Result<WorkspaceConfig> {
+        match self.get_content(Path::new(WORKSPACE_FILE_NAME))? {
+            None => Ok(WorkspaceConfig::default()),
+            Some(content) => parse_config(&content, WORKSPACE_FILE_NAME),
+        }
+    }
+
+    /// the parsed `nabs.json` of a package
+    fn package_config(&self, t: &RawTarget) -> Result<PackageConfig> {
+        let path = self.target_name_to_path(&t.name).join(NABS_FILE_NAME);
+        match self.get_content(&path)? {
+            None => Ok(PackageConfig::default()),
+            Some(content) => parse_config(&content, &path.to_string_lossy()),
+        }
+    }
+
+    /// all package directories (relative to the workspace root) in the workspace, a package is any directory containing `nabs.json`
+    fn get_nabs_packages(&self) -> Vec<PathBuf> {
+        self.list_files(Path::new(""))
+            .into_iter()
+            .filter(|f| f.file_name().is_some_and(|n| n == NABS_FILE_NAME))
+            .filter_map(|f| f.parent().map(PathBuf::from))
+            .collect()
     }
 
     /// given a path relative to a RawTarget, construct a new RawTarget
@@ -247,10 +282,8 @@ pub trait Repository {
         let base = self.target_name_to_path(&rel_to.name);
         let rel_to_base = rel_path.get_host_path();
         let path = base.join(rel_to_base);
-        let target_name = normalize_path(&path).context(anyhow!(
-            "the path is mostly outside your workspace, path={:?}",
-            path
-        ))?;
+        let target_name =
+            normalize_path(&path).map_err(|_| NabsError::PathEscapesWorkspace { path })?;
         let target_name = target_name.to_str().ok_or(anyhow!(
             "failed in converting path to String: {:?}",
             target_name
@@ -258,6 +291,24 @@ pub trait Repository {
         Ok(RawTarget::from_string_name(String::from(target_name))?)
     }
 
+    /// given a raw target pointing to any directory in the workspace, find the package it belongs to
+    /// this is the closest ancestor (including itself) which contains `nabs.json`
+    /// returns `None` if the directory is not inside any package
+    fn enclosing_package(&self, t: &RawTarget) -> Option<RawTarget> {
+        let mut cur = Some(t.name.to_string_ref().as_str());
+        while let Some(raw_name) = cur {
+            // every prefix of a valid target name is also a valid target name
+            let name = TargetName(raw_name.to_string());
+            let marker = self.target_name_to_path(&name).join(NABS_FILE_NAME);
+            // an unreadable `nabs.json` still marks a package
+            if !matches!(self.get_content(&marker), Ok(None)) {
+                return Some(RawTarget::new(name));
+            }
+            cur = raw_name.rsplit_once('/').map(|(parent, _)| parent);
+        }
+        None
+    }
+
     /// a standard target-name like packages/python/qsync_stream
     /// convert it to a path in the host system
     fn target_name_to_path(&self, t: &TargetName) -> PathBuf {
@@ -272,6 +323,8 @@ pub trait Repository {
 #[derive(Debug, Clone)]
 pub struct Monorepo {
     workspace_path: PathBuf,
+    // paths which can't be listed are reported here
+    diagnostics: Arc<Diagnostics>,
 }
 
 impl Monorepo {
@@ -283,11 +336,12 @@ impl Monorepo {
 
         let mut search_path = cwd.as_path();
         loop {
-            let workspace_file = search_path.join("workspace.json");
+            let workspace_file = search_path.join(WORKSPACE_FILE_NAME);
             if workspace_file.exists() {
                 info!("workspace-path={}", search_path.to_string_lossy());
                 return Ok(Monorepo {
                     workspace_path: search_path.to_path_buf(),
+                    diagnostics: Arc::new(Diagnostics::default()),
                 });
             }
 
@@ -296,18 +350,45 @@ impl Monorepo {
                 None => break,
             }
         }
-        bail!("Could not find workspace.json in current directory or any parent directory")
+        Err(NabsError::WorkspaceNotFound { searched_from: cwd }.into())
+    }
+
+    /// report paths which can't be listed to `diagnostics`, by default they are printed as text
+    pub fn with_diagnostics(mut self, diagnostics: Arc<Diagnostics>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+}
+
+/// the path an error from walking a directory is about, if it has one
+fn walk_error_path(e: &ignore::Error) -> Option<&Path> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            walk_error_path(err)
+        }
+        _ => None,
     }
 }
 
 impl Repository for Monorepo {
-    fn get_content(&self, path: &Path) -> Option<String> {
-        match std::fs::read_to_string(path) {
-            Ok(s) => Some(s),
+    fn get_content(&self, path: &Path) -> Result<Option<String>> {
+        // paths are relative to the workspace, not to the directory nabs was invoked from
+        match std::fs::read_to_string(self.workspace_root().join(path)) {
+            Ok(s) => Ok(Some(s)),
             Err(e) => {
                 match e.kind() {
-                    ErrorKind::NotFound => None,
-                    _ => panic!("{:?}", e),
+                    ErrorKind::NotFound => Ok(None),
+                    // manifest names like `BUILD` can clash with a `build/` directory on case-insensitive file systems
+                    ErrorKind::IsADirectory => Ok(None),
+                    // a component of the path is a file, there is nothing here
+                    ErrorKind::NotADirectory => Ok(None),
+                    _ => Err(NabsError::UnreadableFile {
+                        path: path.to_path_buf(),
+                        message: e.to_string(),
+                    }
+                    .into()),
                 }
             }
         }
@@ -316,16 +397,48 @@ impl Repository for Monorepo {
     fn workspace_root(&self) -> &Path {
         &self.workspace_path
     }
+
+    fn list_files(&self, dir: &Path) -> Vec<PathBuf> {
+        ignore::Walk::new(self.workspace_root().join(dir))
+            .filter_map(|v| match v {
+                Err(e) => {
+                    let path = walk_error_path(&e)
+                        .map(|p| p.strip_prefix(self.workspace_root()).unwrap_or(p))
+                        .unwrap_or(dir);
+                    self.diagnostics.warn(Warning {
+                        kind: WarningKind::UnreadablePath,
+                        package: dir.to_string_lossy().to_string(),
+                        subject: path.to_string_lossy().to_string(),
+                        reason: e.to_string(),
+                        location: None,
+                    });
+                    None
+                }
+                Ok(entry) => {
+                    let p = entry.path();
+                    if p.is_file() {
+                        p.strip_prefix(self.workspace_root())
+                            .ok()
+                            .map(PathBuf::from)
+                    } else {
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
 }
 
+/// a repository backed by a map from paths to contents, for tests
+#[cfg(any(test, feature = "testing"))]
 #[derive(Debug, Clone)]
 pub struct MockRepo {
     fake: HashMap<String, String>,
     workspace_path: PathBuf,
 }
 
+#[cfg(any(test, feature = "testing"))]
 impl MockRepo {
-    #[allow(unused)]
     pub fn new(path_by_content: HashMap<String, String>, workspace_path: PathBuf) -> MockRepo {
         MockRepo {
             fake: path_by_content,
@@ -334,13 +447,21 @@ impl MockRepo {
     }
 }
 
+#[cfg(any(test, feature = "testing"))]
 impl Repository for MockRepo {
-    fn get_content(&self, path: &Path) -> Option<String> {
-        self.fake.get(path.to_str().unwrap()).map(|c| c.clone())
+    fn get_content(&self, path: &Path) -> Result<Option<String>> {
+        Ok(self.fake.get(path.to_str().unwrap()).cloned())
     }
     fn workspace_root(&self) -> &Path {
         &self.workspace_path
     }
+    fn list_files(&self, dir: &Path) -> Vec<PathBuf> {
+        self.fake
+            .keys()
+            .map(PathBuf::from)
+            .filter(|p| p.starts_with(dir))
+            .collect()
+    }
 }
 
 #[cfg(test)]
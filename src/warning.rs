@@ -0,0 +1,207 @@
+/// warnings are problems nabs can work around, the graph is still built but it might be missing some edges
+/// they are printed as they happen and collected, so that strict mode (`--strict`) can fail on them
+/// known warnings can be allowed in `workspace.json`, strict mode ignores these
+use std::fmt::Display;
+
+use anyhow::{Context, Result, anyhow};
+use globset::Glob;
+use serde::{Deserialize, Serialize};
+
+use crate::config::AllowedWarning;
+use crate::diagnostics::Location;
+use crate::error::NabsError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    /// a dependency in a manifest which could not be resolved to a package (`FailedParent`)
+    FailedParent,
+    /// a dependency was found, but inference failed for it, the dependency is missing from the graph
+    ParentInferenceFailed,
+    /// a package was skipped because one of its files could not be read
+    UnreadablePackage,
+    /// a changed file which is not inside any package
+    FileNotInPackage,
+    /// a path which could not be listed, the files inside it are missing from every package
+    UnreadablePath,
+    /// the inference cache could not be written, the next run infers every package again
+    CacheNotSaved,
+    /// a path dependency in `Cargo.toml` which is never used in code, found by `nabs check`
+    CargoUnusedPathDependency,
+    /// a workspace crate used in code without a path dependency on it, found by `nabs check`
+    CargoUndeclaredWorkspaceCrate,
+    /// a changed package without any target in the graph, nothing depending on it is in the changeset
+    PackageWithoutTarget,
+}
+
+impl WarningKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            WarningKind::FailedParent => "failed-parent",
+            WarningKind::ParentInferenceFailed => "parent-inference-failed",
+            WarningKind::UnreadablePackage => "unreadable-package",
+            WarningKind::FileNotInPackage => "file-not-in-package",
+            WarningKind::UnreadablePath => "unreadable-path",
+            WarningKind::CacheNotSaved => "cache-not-saved",
+            WarningKind::CargoUnusedPathDependency => "cargo-unused-path-dependency",
+            WarningKind::CargoUndeclaredWorkspaceCrate => "cargo-undeclared-workspace-crate",
+            WarningKind::PackageWithoutTarget => "package-without-target",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// the package the warning is about, empty for files outside every package
+    pub package: String,
+    /// what went wrong in the package, like the dependency or the file
+    pub subject: String,
+    pub reason: String,
+    /// where in the package's manifests the problem is, if the inferrer knows it
+    pub location: Option<Location>,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            WarningKind::FailedParent => write!(
+                f,
+                "failed parsing dependency for package={} dependency={}, reason={}",
+                self.package, self.subject, self.reason
+            ),
+            WarningKind::ParentInferenceFailed => write!(
+                f,
+                "failed in creating graph for package={}. nabs will skip adding this target in analysis. This package was found in the dependencies of {}\nreason:\n{}",
+                self.subject, self.package, self.reason
+            ),
+            WarningKind::UnreadablePackage => write!(
+                f,
+                "failed in creating graph for package={}. nabs will skip adding this target in analysis\nreason:\n{}",
+                self.package, self.reason
+            ),
+            WarningKind::FileNotInPackage => {
+                write!(f, "file={} is not part of any package", self.subject)
+            }
+            WarningKind::UnreadablePath => write!(
+                f,
+                "could not read path={}, skipping analysis for this path and its children\nreason:\n{}",
+                self.subject, self.reason
+            ),
+            WarningKind::CacheNotSaved => {
+                write!(f, "failed in saving the inference cache, {}", self.reason)
+            }
+            WarningKind::CargoUnusedPathDependency | WarningKind::CargoUndeclaredWorkspaceCrate => {
+                write!(f, "package={} {}", self.package, self.reason)
+            }
+            WarningKind::PackageWithoutTarget => write!(
+                f,
+                "package={} changed, but no target was inferred for it. packages depending on it are missing from the changeset",
+                self.package
+            ),
+        }
+    }
+}
+
+impl Warning {
+    /// `package` in an allowlist entry is a glob, the other fields are compared as is
+    /// a field not set in the entry matches anything
+    fn is_allowed_by(&self, allowed: &AllowedWarning) -> Result<bool> {
+        if allowed.code != self.kind.code() {
+            return Ok(false);
+        }
+        if let Some(package) = &allowed.package {
+            let glob = Glob::new(package)
+                .context(anyhow!(
+                    "invalid package glob in allow_warnings={}",
+                    package
+                ))?
+                .compile_matcher();
+            if !glob.is_match(&self.package) {
+                return Ok(false);
+            }
+        }
+        Ok(allowed.subject.as_ref().is_none_or(|s| *s == self.subject))
+    }
+}
+
+/// the warnings not covered by `allowed`
+pub fn denied<'a>(warnings: &'a [Warning], allowed: &[AllowedWarning]) -> Result<Vec<&'a Warning>> {
+    let mut denied = Vec::new();
+    for w in warnings {
+        let mut is_allowed = false;
+        for a in allowed {
+            is_allowed |= w.is_allowed_by(a)?;
+        }
+        if !is_allowed {
+            denied.push(w);
+        }
+    }
+    Ok(denied)
+}
+
+/// fails with `NabsError::DeniedWarnings` if any warning is not allowed, every such warning is printed as a report
+pub fn deny_warnings(warnings: &[Warning], allowed: &[AllowedWarning]) -> Result<()> {
+    let denied = denied(warnings, allowed)?;
+    if denied.is_empty() {
+        return Ok(());
+    }
+    eprintln!("strict mode, found {} warnings:", denied.len());
+    for w in &denied {
+        eprintln!(
+            "  [{}] package={} subject={}",
+            w.kind.code(),
+            w.package,
+            w.subject
+        );
+    }
+    Err(NabsError::DeniedWarnings {
+        count: denied.len(),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::AllowedWarning;
+
+    use super::{Warning, WarningKind, denied};
+
+    #[test]
+    fn test_denied() {
+        let warning = |kind, package: &str, subject: &str| Warning {
+            kind,
+            package: package.to_string(),
+            subject: subject.to_string(),
+            reason: "".to_string(),
+            location: None,
+        };
+        let warnings = vec![
+            warning(WarningKind::FailedParent, "libs/a", "../../outside"),
+            warning(WarningKind::FailedParent, "libs/b", "${ROOT}/x"),
+            warning(WarningKind::FailedParent, "apps/c", "${ROOT}/x"),
+            warning(WarningKind::FileNotInPackage, "", "README.md"),
+        ];
+        let allowed = vec![
+            AllowedWarning {
+                code: "failed-parent".to_string(),
+                package: Some("libs/*".to_string()),
+                subject: Some("${ROOT}/x".to_string()),
+            },
+            AllowedWarning {
+                code: "file-not-in-package".to_string(),
+                package: None,
+                subject: None,
+            },
+        ];
+        let got: Vec<(&str, &str)> = denied(&warnings, &allowed)
+            .unwrap()
+            .iter()
+            .map(|w| (w.package.as_str(), w.subject.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            vec![("libs/a", "../../outside"), ("apps/c", "${ROOT}/x")]
+        );
+    }
+}
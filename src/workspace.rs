@@ -0,0 +1,127 @@
+/// entry point for using nabs as a library
+/// a `Workspace` is a repository along with its `workspace.json`, everything else (packages, inferrers, the graph) is derived from it
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use log::info;
+
+use crate::cache::{InferenceCache, RecordingRepo};
+use crate::config::WorkspaceConfig;
+use crate::diagnostics::Diagnostics;
+use crate::graph::TargetGraph;
+use crate::infer::InferRunner;
+use crate::reverse_index::ReverseIndex;
+use crate::types::{Monorepo, RawTarget, Repository, Target};
+use crate::warning::{Warning, deny_warnings};
+
+pub struct Workspace {
+    repo: Arc<dyn Repository>,
+    config: WorkspaceConfig,
+    diagnostics: Arc<Diagnostics>,
+    cache: bool,
+}
+
+impl Workspace {
+    /// the workspace enclosing the current directory
+    pub fn open() -> Result<Self> {
+        Self::from_repo(Arc::new(Monorepo::new()?))
+    }
+
+    /// a workspace over any repository, like a `MockRepo` in tests
+    pub fn from_repo(repo: Arc<dyn Repository>) -> Result<Self> {
+        let config = repo.workspace_config()?;
+        Ok(Self {
+            repo,
+            config,
+            diagnostics: Arc::new(Diagnostics::default()),
+            cache: false,
+        })
+    }
+
+    /// report warnings to `diagnostics`, by default they are printed as text
+    pub fn with_diagnostics(mut self, diagnostics: Arc<Diagnostics>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// reuse targets inferred by earlier runs from `.nabs/cache`, see `InferenceCache`
+    /// this has no effect if process plugins are configured, nabs can't know which files they read
+    pub fn with_cache(mut self, enabled: bool) -> Self {
+        self.cache = enabled;
+        self
+    }
+
+    pub fn repo(&self) -> &Arc<dyn Repository> {
+        &self.repo
+    }
+
+    pub fn config(&self) -> &WorkspaceConfig {
+        &self.config
+    }
+
+    pub fn diagnostics(&self) -> &Arc<Diagnostics> {
+        &self.diagnostics
+    }
+
+    /// every package in the workspace, sorted by name
+    pub fn packages(&self) -> Result<Vec<RawTarget>> {
+        let mut pkgs = Vec::new();
+        for p in self.repo.get_nabs_packages() {
+            let name = p
+                .to_str()
+                .ok_or(anyhow!("could not parse path: {:?}", p))?
+                .to_string();
+            pkgs.push(RawTarget::from_string_name(name)?);
+        }
+        pkgs.sort_by(|a, b| a.name.to_string_ref().cmp(b.name.to_string_ref()));
+        info!("all detected packages, {:?}", pkgs);
+        Ok(pkgs)
+    }
+
+    /// the default inferrers along with the ones configured in `workspace.json`
+    /// use `InferRunner::new` directly to build a graph with custom inferrers
+    pub fn runner(&self) -> Result<InferRunner> {
+        let runner = if self.cache && self.config.plugins.is_empty() {
+            let recording: Arc<dyn Repository> =
+                Arc::new(RecordingRepo::new(Arc::clone(&self.repo)));
+            let cache = InferenceCache::load(Arc::clone(&self.repo))?;
+            InferRunner::default(&recording, &self.config)?.with_cache(Arc::new(cache))
+        } else {
+            InferRunner::default(&self.repo, &self.config)?
+        };
+        Ok(runner.with_diagnostics(Arc::clone(&self.diagnostics)))
+    }
+
+    /// graph of every package in the workspace, along with the targets detected for them
+    pub fn build_graph(&self) -> Result<(TargetGraph, Vec<Target>)> {
+        self.runner()?.build_graph(self.packages()?)
+    }
+
+    /// graph of the packages which could be affected by a change in `changed`, enough to answer `rdeps` for them
+    /// these are found with a `ReverseIndex` of the paths mentioned in manifests, no other package is inferred
+    /// builds the whole graph if some inferrer can't tell which files it reads parents from
+    pub fn build_rdeps_graph(&self, changed: &[RawTarget]) -> Result<(TargetGraph, Vec<Target>)> {
+        let runner = self.runner()?;
+        let packages = self.packages()?;
+        let Some(manifests) = runner.manifests() else {
+            info!("some inferrers don't list their manifests, building the whole graph");
+            return runner.build_graph(packages);
+        };
+        let index = ReverseIndex::build(self.repo.as_ref(), &packages, &manifests)?;
+        let affected = index.dependents(changed);
+        info!(
+            "inferring {} of {} packages reachable from {:?}",
+            affected.len(),
+            packages.len(),
+            changed
+        );
+        let (start, excluded): (Vec<RawTarget>, Vec<RawTarget>) =
+            packages.into_iter().partition(|p| affected.contains(p));
+        runner.build_graph_excluding(start, &HashSet::from_iter(excluded))
+    }
+
+    /// fails if any of `warnings` is not allowed in `workspace.json`
+    pub fn deny_warnings(&self, warnings: &[Warning]) -> Result<()> {
+        deny_warnings(warnings, &self.config.allow_warnings)
+    }
+}